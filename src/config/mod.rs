@@ -0,0 +1,445 @@
+use crate::i18n::Lang;
+use image::ImageFormat;
+use log::warn;
+use ocrs::DecodeMethod;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+pub const CONFIG_FILE: &str = "config.json";
+
+// Most Pokemon one battle can show: a horde.
+pub const HORDE_SIZE: usize = 5;
+
+// Polling faster than this only burns CPU.
+pub const MIN_POLL_MS: u64 = 5;
+
+// Default `rate_smoothing`, also used when it is not a number.
+const DEFAULT_RATE_SMOOTHING: f64 = 0.2;
+
+// User settings loaded from config.json. Missing fields fall back to their defaults.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    // Write state.json after this many counted encounters (0 = every encounter).
+    pub save_interval: u32,
+    // Also copy state.json into backups/ on every Nth save, keeping the newest `backup_keep`
+    // copies. Off (0) unless set.
+    pub backup_every: u32,
+    pub backup_keep: usize,
+    // Language of the app's labels and buttons.
+    pub language: Lang,
+    // Count the same species twice when it shows up twice in one battle.
+    pub count_duplicates: bool,
+    // Whether `encounters` counts Pokemon or battles. `mon_stats` always has every Pokemon.
+    pub count_unit: CountUnit,
+    // A frame with more names than this is OCR noise and isn't counted. 2 fits singles and
+    // doubles, horde mode raises it; 0 is no limit.
+    pub max_mons_per_encounter: usize,
+    // Hunting hordes: a battle is one encounter, every Pokemon in it (duplicates too) goes into
+    // the stats, and up to a horde's worth of names is expected whatever the settings above say.
+    pub horde_mode: bool,
+    // Ignore OCR lines scoring below this (0.0 - 1.0). 0.0 keeps every line.
+    pub min_confidence: f32,
+    // Read the names and the bottom strip at the same time on a small pool of their own.
+    // Turn off on machines with few cores, where it competes with OCR's own threads.
+    pub use_parallel: bool,
+    // Discord webhook notified when a target or shiny shows up.
+    pub webhook_url: Option<String>,
+    // Species (case-insensitive) that trigger the webhook.
+    pub target_mons: Vec<String>,
+    // WAV/OGG played when an encounter is counted, and instead of it for shinies and targets.
+    pub sound_on_encounter: Option<String>,
+    pub sound_on_target: Option<String>,
+    // Max edits when snapping an OCR'd name to a known species. Names further off are dropped.
+    pub name_match_distance: usize,
+    // Append every counted encounter to `event_log_path` as one JSON line.
+    pub event_log: bool,
+    pub event_log_path: String,
+    // Stop reading a game window while it is minimized, so stale frames are not counted.
+    pub pause_when_unfocused: bool,
+    // Worker sleep between polls while watching for an encounter, during one, and while no
+    // game window is found.
+    pub poll_idle_ms: u64,
+    pub poll_active_ms: u64,
+    pub poll_no_window_ms: u64,
+    // Convert crops to grayscale before OCR. Some UI themes read better in color.
+    pub grayscale: bool,
+    // Luminance (0-255) splitting pixels into black and white before OCR. Overrides grayscale.
+    pub threshold: Option<u8>,
+    pub hotkeys: Hotkeys,
+    // Weight of the newest interval in the smoothed rate (0.01 - 1.0). Lower is steadier, 1.0
+    // shows the last interval as is.
+    pub rate_smoothing: f64,
+    // How far (0-255) a cell of the bottom strip's thumbnail may change from the last strip
+    // without a wild phrase before it is read again. 0 re-reads on any pixel change.
+    pub motion_threshold: u8,
+    // Ignore a new count in the same window this soon after the previous one.
+    pub min_encounter_gap_ms: u64,
+    // Polls after a count during which names can't count again, i.e. a victory screen that
+    // still shows "Lv.". 0 turns it off.
+    pub rearm_cooldown_frames: u32,
+    // How many encounters the history list (and state.json) keeps.
+    pub recent_encounters_len: usize,
+    // Keep the counter window above the game, i.e. a fullscreen-windowed one.
+    pub always_on_top: bool,
+    // Shrink the window to a see-through count and chain without title bar, for an overlay.
+    pub compact_mode: bool,
+    // Text file rewritten on every count, for stream overlays. None turns it off.
+    pub overlay_file: Option<String>,
+    pub overlay_format: String,
+    // Count the opposing Pokemon of trainer battles too.
+    pub count_trainers: bool,
+    // Pause counting after this many seconds without an encounter. None (or 0) never pauses.
+    pub auto_pause_after_secs: Option<u64>,
+    // Text in the bottom strip that starts a wild encounter, per client language. Any one matches.
+    pub wild_phrases: Vec<String>,
+    // Text in the bottom strip that opens a trainer battle instead.
+    pub trainer_phrases: Vec<String>,
+    // Text in the bottom strip that ends a battle, i.e. "got away safely". Empty by default:
+    // watching for it reads the strip on every poll in battle, and a short word like "fled"
+    // also shows up in move messages.
+    pub battle_end_phrases: Vec<String>,
+    // Level abbreviations shown right after a Pokemon name, per client language.
+    pub level_markers: Vec<String>,
+    // Most words tried as one Pokemon name before a level marker, i.e. 2 for "mr. mime".
+    pub max_name_tokens: usize,
+    // Encounter totals that pop up a desktop notification when reached.
+    pub milestones: Vec<u32>,
+    pub milestone_notifications: bool,
+    // "window" reads the game window. "monitor" reads `monitor_rect` of monitor `monitor_index`
+    // instead, for compositors where window capture comes back black.
+    pub capture_mode: CaptureMode,
+    pub monitor_index: usize,
+    pub monitor_rect: Option<[u32; 4]>, // x, y, width, height; None is the whole monitor
+    // Recent encounters are gray for species at least this share of all encounters (in %), and
+    // gold for those at most `rarity_rare_percent`.
+    pub rarity_common_percent: f32,
+    pub rarity_rare_percent: f32,
+    // Species in the dex being completed, i.e. 649 for Gen 1-5. Shows a completion percentage.
+    pub dex_total: Option<u32>,
+    // Species being hunted. Counts encounters since it last appeared (the "phase").
+    pub target_species: Option<String>,
+    // Polls after a wild phrase that look for the names before giving up. 0 never gives up.
+    pub wild_grace_frames: u32,
+    // Beam width for reading text. None reads greedily (fastest); 5-10 can fix misread letters.
+    pub ocr_beam_width: Option<u32>,
+    // Only these characters may be recognized. None allows ocrs' whole alphabet.
+    pub ocr_allowed_chars: Option<String>,
+    // With debug on, keep every capture in this folder instead of overwriting debug.png.
+    pub debug_dir: Option<String>,
+    // How many captures `debug_dir` keeps, oldest removed first.
+    pub debug_keep: usize,
+    // Debug capture file type: "png", "jpeg" or "webp".
+    pub debug_format: String,
+}
+
+// What one encounter is: each Pokemon (a horde of 5 adds 5) or each battle.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CountUnit {
+    #[default]
+    Mons,
+    Battles,
+}
+
+impl CountUnit {
+    // How much a battle with `mons` Pokemon adds to the encounter count.
+    pub fn increment(self, mons: usize) -> u32 {
+        match self {
+            CountUnit::Mons => mons as u32,
+            CountUnit::Battles => 1,
+        }
+    }
+}
+
+// What the counter captures: the game window, or a fixed part of a monitor.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureMode {
+    #[default]
+    Window,
+    Monitor,
+}
+
+// What a hotkey asks the app to do, the same as the button of that name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HotkeyAction {
+    Start,
+    Pause,
+    Reset,
+    NewSession,
+    Undo,
+    Quit,
+    Compact,
+}
+
+// Keys for the main buttons while the counter window is focused, by egui key name (i.e. "S",
+// "F5", "Space").
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Hotkeys {
+    pub start: String,
+    pub pause: String,
+    pub reset: String,
+    pub new_session: String,
+    pub undo: String,
+    pub quit: String,
+    pub compact: String,
+    // Also catch the keys while the game has focus, held with `global_modifiers`. Needs a build
+    // with the global-hotkeys feature.
+    pub global: bool,
+    // Modifiers for the system-wide keys, joined with "+", i.e. "ctrl+shift" or "alt".
+    pub global_modifiers: String,
+}
+
+impl Hotkeys {
+    // Each key with the action it runs.
+    pub fn bindings(&self) -> [(&str, HotkeyAction); 7] {
+        [
+            (&self.start, HotkeyAction::Start),
+            (&self.pause, HotkeyAction::Pause),
+            (&self.reset, HotkeyAction::Reset),
+            (&self.new_session, HotkeyAction::NewSession),
+            (&self.undo, HotkeyAction::Undo),
+            (&self.quit, HotkeyAction::Quit),
+            (&self.compact, HotkeyAction::Compact),
+        ]
+    }
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self {
+            start: "S".to_string(),
+            pause: "P".to_string(),
+            reset: "R".to_string(),
+            new_session: "N".to_string(),
+            undo: "U".to_string(),
+            quit: "Q".to_string(),
+            compact: "C".to_string(),
+            global: false,
+            global_modifiers: "ctrl+shift".to_string(),
+        }
+    }
+}
+
+fn parse_image_format(name: &str) -> Option<ImageFormat> {
+    match name.trim().to_lowercase().as_str() {
+        "png" => Some(ImageFormat::Png),
+        "jpeg" | "jpg" => Some(ImageFormat::Jpeg),
+        "webp" => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+fn to_strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|item| item.to_string()).collect()
+}
+
+// English "a wild", German "ein wildes", French "sauvage", Spanish "salvaje", Italian
+// "selvatico", Portuguese "selvagem".
+fn default_wild_phrases() -> Vec<String> {
+    to_strings(&["a wild", "ein wildes", "sauvage", "salvaje", "selvatico", "selvagem"])
+}
+
+fn default_trainer_phrases() -> Vec<String> {
+    to_strings(&["would like to battle", "wants to battle", "challenged by"])
+}
+
+// English/German "lv."/"lvl", Italian "liv.", French/Spanish/Portuguese "nv."/"niv."/"nív.".
+fn default_level_markers() -> Vec<String> {
+    to_strings(&["lv.", "lvl", "lvl.", "liv.", "nv.", "niv.", "nív."])
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            save_interval: 5,
+            backup_every: 0,
+            backup_keep: 20,
+            count_duplicates: true,
+            count_unit: CountUnit::Mons,
+            language: Lang::En,
+            max_mons_per_encounter: 2,
+            horde_mode: false,
+            min_confidence: 0.0,
+            use_parallel: true,
+            webhook_url: None,
+            sound_on_encounter: None,
+            sound_on_target: None,
+            target_mons: vec![],
+            name_match_distance: 2,
+            event_log: false,
+            event_log_path: "encounters.jsonl".to_string(),
+            pause_when_unfocused: false,
+            poll_idle_ms: 10,
+            poll_active_ms: 100,
+            poll_no_window_ms: 50,
+            grayscale: true,
+            threshold: None,
+            hotkeys: Hotkeys::default(),
+            rate_smoothing: DEFAULT_RATE_SMOOTHING,
+            motion_threshold: 0,
+            min_encounter_gap_ms: 1500,
+            rearm_cooldown_frames: 0,
+            recent_encounters_len: 50,
+            always_on_top: false,
+            compact_mode: false,
+            overlay_file: None,
+            overlay_format: "Encounters: {count} | Chain: {chain}".to_string(),
+            count_trainers: false,
+            auto_pause_after_secs: None,
+            wild_phrases: default_wild_phrases(),
+            trainer_phrases: default_trainer_phrases(),
+            battle_end_phrases: vec![],
+            level_markers: default_level_markers(),
+            max_name_tokens: 2,
+            milestones: vec![100, 500, 1000, 2500, 5000, 10000, 25000, 50000, 100000],
+            milestone_notifications: true,
+            capture_mode: CaptureMode::Window,
+            monitor_index: 0,
+            monitor_rect: None,
+            rarity_common_percent: 10.0,
+            rarity_rare_percent: 1.0,
+            dex_total: None,
+            target_species: None,
+            wild_grace_frames: 0,
+            ocr_beam_width: None,
+            ocr_allowed_chars: None,
+            debug_dir: None,
+            debug_keep: 500,
+            debug_format: "png".to_string(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Config {
+        let config_json = match fs::read_to_string(CONFIG_FILE) {
+            Ok(json) => json,
+            Err(_) => return Config::default(),
+        };
+        match serde_json::from_str::<Config>(&config_json) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Failed to parse {CONFIG_FILE} ({e}), using default settings.");
+                Config::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        fs::write(CONFIG_FILE, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // Problems that would make a setting misbehave, worded for the settings panel in the
+    // configured language.
+    pub fn validate(&self) -> Vec<String> {
+        let lang = self.language;
+        let mut errors = vec![];
+        if !(0.0..=1.0).contains(&self.min_confidence) {
+            errors.push(lang.tr("Min confidence must be between 0.0 and 1.0.").to_string());
+        }
+        if let Some(url) = &self.webhook_url {
+            if !url.starts_with("https://") {
+                errors.push(lang.tr("Webhook URL must start with https://.").to_string());
+            }
+        }
+        for path in [&self.sound_on_encounter, &self.sound_on_target].into_iter().flatten() {
+            if !path.is_empty() && !Path::new(path).is_file() {
+                errors.push(lang.tr("Sound file not found: {}.").replace("{}", path));
+            }
+        }
+        if self.wild_phrases.iter().all(|phrase| phrase.trim().is_empty()) {
+            errors.push(lang.tr("At least one wild phrase is needed.").to_string());
+        }
+        if let Some([_, _, width, height]) = self.monitor_rect {
+            if width == 0 || height == 0 {
+                errors.push(lang.tr("Monitor rect needs a width and height.").to_string());
+            }
+        }
+        if parse_image_format(&self.debug_format).is_none() {
+            errors.push(lang.tr("Debug format must be png, jpeg or webp.").to_string());
+        }
+        if self.level_markers.iter().all(|marker| marker.trim().is_empty()) {
+            errors.push(lang.tr("At least one level marker is needed.").to_string());
+        }
+        errors
+    }
+
+    // `count_unit`, except that horde mode always counts battles.
+    pub fn effective_count_unit(&self) -> CountUnit {
+        if self.horde_mode {
+            CountUnit::Battles
+        } else {
+            self.count_unit
+        }
+    }
+
+    // `count_duplicates`, always on in horde mode, where five of one species is normal.
+    pub fn effective_count_duplicates(&self) -> bool {
+        self.count_duplicates || self.horde_mode
+    }
+
+    // `max_mons_per_encounter`, raised to fit a horde in horde mode. 0 is still no limit.
+    pub fn effective_max_mons(&self) -> usize {
+        if self.horde_mode && self.max_mons_per_encounter > 0 {
+            self.max_mons_per_encounter.max(HORDE_SIZE)
+        } else {
+            self.max_mons_per_encounter
+        }
+    }
+
+    // A save interval of 0 would never trigger, so it means "save every encounter".
+    pub fn effective_save_interval(&self) -> u32 {
+        self.save_interval.max(1)
+    }
+
+    pub fn poll_idle(&self) -> u64 {
+        self.poll_idle_ms.max(MIN_POLL_MS)
+    }
+
+    pub fn poll_active(&self) -> u64 {
+        self.poll_active_ms.max(MIN_POLL_MS)
+    }
+
+    pub fn poll_no_window(&self) -> u64 {
+        self.poll_no_window_ms.max(MIN_POLL_MS)
+    }
+
+    pub fn ocr_decode_method(&self) -> DecodeMethod {
+        match self.ocr_beam_width {
+            Some(width) if width > 1 => DecodeMethod::BeamSearch { width },
+            _ => DecodeMethod::Greedy,
+        }
+    }
+
+    // Unknown names fall back to PNG, so a typo never stops debug captures.
+    pub fn debug_image_format(&self) -> ImageFormat {
+        parse_image_format(&self.debug_format).unwrap_or_else(|| {
+            static WARNED: AtomicBool = AtomicBool::new(false);
+            if !WARNED.swap(true, Ordering::Relaxed) {
+                warn!("Unknown debug_format {:?}, saving as PNG.", self.debug_format);
+            }
+            ImageFormat::Png
+        })
+    }
+
+    pub fn rate_smoothing_factor(&self) -> f64 {
+        if self.rate_smoothing.is_nan() {
+            return DEFAULT_RATE_SMOOTHING;
+        }
+        self.rate_smoothing.clamp(0.01, 1.0)
+    }
+
+    pub fn auto_pause_after(&self) -> Option<Duration> {
+        self.auto_pause_after_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+}
@@ -1,17 +1,50 @@
+use crate::config::{Config, CountUnit, HORDE_SIZE};
+use crate::notify;
+use crate::i18n::Lang;
+use crate::overlay;
+use crate::sound;
+use crate::source::FrameSource;
+use crate::species;
+use chrono::{DateTime, Utc};
 use core::panic;
-use image::{DynamicImage, RgbImage};
+use image::{DynamicImage, ImageFormat, RgbImage, RgbaImage};
+use log::{debug, info, warn};
+use once_cell::sync::OnceCell;
 use ocrs::{ImageSource, OcrEngine};
-use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
-use std::fs;
-use std::sync::atomic::AtomicU8;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
 use xcap::Window; // Required for io::Error
 
 pub const APP_NAME: &str = "pokemmo";
 pub const JAVA: &str = "java";
 
+// Lowercased text that marks a shiny in the encounter message.
+pub const SHINY_MARKERS: &[&str] = &["shiny"];
+
+
+// The map name region changes with every step, so it is read at most this often per window.
+pub const LOCATION_READ_INTERVAL: Duration = Duration::from_secs(5);
+
+// Threads for reading the two crops at once. OCR itself runs on the global pool.
+const OCR_JOIN_THREADS: usize = 2;
+
+// Frames whose stage times are averaged into each debug log line and the UI breakdown.
+pub const FRAME_TIMING_WINDOW: u32 = 50;
+
+// Consecutive failed polls before the worker gives up and pauses counting.
+pub const MAX_CAPTURE_FAILURES: u32 = 10;
+
 // AtomicU8 for global app state
 pub static APP_STATE: AtomicU8 = AtomicU8::new(STATE_IDLE);
 
@@ -27,9 +60,74 @@ pub struct EncounterState {
     pub last_encounter: Vec<String>,
     pub mon_stats: HashMap<String, u32>,
     pub debug: bool,
-    pub in_encounter: bool,
-    pub is_not_counted: bool,
     pub unsaved_encounters: u32, // ✅ Move it inside EncounterState
+    #[serde(default)]
+    pub shiny_encounters: u32,
+    #[serde(default)]
+    pub shiny_mons: Vec<String>,
+    #[serde(default)]
+    pub sessions: Vec<Session>,
+    #[serde(default)]
+    pub last_encounter_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub recent_encounters: VecDeque<RecentEncounter>, // Newest last, capped by config
+    #[serde(default)]
+    pub total_playtime_secs: u64, // Time spent counting, across every session
+    // Start of the counting stretch not yet in `total_playtime_secs`. Not saved, so time lost to
+    // a crash is dropped rather than counted on restore.
+    #[serde(skip)]
+    pub playtime_mark: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub current_chain: u32, // Same species seen this many times in a row
+    #[serde(default)]
+    pub chain_species: Option<String>,
+    #[serde(default)]
+    pub encounters_since_target: u32, // Reset when `target_species` shows up
+    #[serde(default)]
+    pub last_milestone: u32, // Highest milestone already announced, so it fires once
+    #[serde(default)]
+    pub location_stats: HashMap<String, HashMap<String, u32>>, // Location -> species -> count
+    #[serde(default)]
+    pub level_stats: HashMap<String, LevelStats>, // Levels read per species. Undo leaves it be
+    #[serde(skip)]
+    pub current_location: Option<String>, // Most recent location read from any window
+    // Detection state per game window, keyed by window id. Ids change between runs, so not saved.
+    #[serde(skip)]
+    pub windows: HashMap<u32, WindowState>,
+    #[serde(skip)]
+    pub last_window: Option<u32>, // Window that produced `last_encounter`
+    #[serde(skip)]
+    pub dry_run: bool, // Never write state.json, i.e. for --replay
+    // Diagnostics for this run: polls that went through OCR, and those that saw a battle.
+    #[serde(skip)]
+    pub frames_processed: u64,
+    #[serde(skip)]
+    pub frames_with_encounter: u64,
+    #[serde(skip)]
+    pub frame_timings: FrameTimings, // Only recorded with debug on
+    // Heartbeat for the UI: last poll that captured a game window, and last one OCR read text in.
+    #[serde(skip)]
+    pub last_window_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub last_text_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub smoothed_interval_secs: Option<f64>, // EWMA of seconds per encounter this session
+    #[serde(skip)]
+    pub mode: PipelineMode,
+    // What OCR read in the last poll that ran it, for the detection panel.
+    #[serde(skip)]
+    pub detected_wild_lines: Vec<String>,
+    #[serde(skip)]
+    pub detected_mon_lines: Vec<String>,
+}
+
+// `DetectOnly` still runs OCR and records what it read, but leaves the counts and the battle
+// tracking alone, i.e. for trying out new crop regions.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PipelineMode {
+    #[default]
+    Count,
+    DetectOnly,
 }
 
 impl Default for EncounterState {
@@ -39,23 +137,572 @@ impl Default for EncounterState {
             last_encounter: vec![],
             mon_stats: HashMap::new(),
             debug: false,
+            unsaved_encounters: 0, // ✅ Initialize here
+            shiny_encounters: 0,
+            shiny_mons: vec![],
+            sessions: vec![],
+            last_encounter_time: None,
+            recent_encounters: VecDeque::new(),
+            total_playtime_secs: 0,
+            playtime_mark: None,
+            current_chain: 0,
+            chain_species: None,
+            encounters_since_target: 0,
+            last_milestone: 0,
+            location_stats: HashMap::new(),
+            level_stats: HashMap::new(),
+            current_location: None,
+            windows: HashMap::new(),
+            last_window: None,
+            dry_run: false,
+            frames_processed: 0,
+            frames_with_encounter: 0,
+            frame_timings: FrameTimings::default(),
+            last_window_at: None,
+            last_text_at: None,
+            smoothed_interval_secs: None,
+            mode: PipelineMode::Count,
+            detected_wild_lines: vec![],
+            detected_mon_lines: vec![],
+        }
+    }
+}
+
+// One counted encounter in the scrolling history.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RecentEncounter {
+    pub at: DateTime<Utc>,
+    pub mons: Vec<String>,
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub window: Option<u32>, // Window that counted it, None for a manual add
+}
+
+// Levels read for one species, for its average, lowest and highest.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct LevelStats {
+    pub count: u32,
+    pub sum: u32,
+    pub min: u32,
+    pub max: u32,
+}
+
+impl LevelStats {
+    pub fn record(&mut self, level: u32) {
+        self.min = if self.count == 0 { level } else { self.min.min(level) };
+        self.max = self.max.max(level);
+        self.count += 1;
+        self.sum += level;
+    }
+
+    fn merge(&mut self, other: LevelStats) {
+        if other.count == 0 {
+            return;
+        }
+        self.min = if self.count == 0 { other.min } else { self.min.min(other.min) };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum += other.sum;
+    }
+
+    pub fn average(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+}
+
+// Time one frame spent in each pipeline stage. The OCR stages add up every crop read, so with
+// `use_parallel` they can exceed the frame's wall time.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StageTimes {
+    pub capture: Duration,
+    pub prepare_input: Duration,
+    pub detect_words: Duration,
+    pub find_text_lines: Duration,
+    pub recognize_text: Duration,
+}
+
+impl StageTimes {
+    pub fn stages(&self) -> [(&'static str, Duration); 5] {
+        [
+            ("capture", self.capture),
+            ("prepare_input", self.prepare_input),
+            ("detect_words", self.detect_words),
+            ("find_text_lines", self.find_text_lines),
+            ("recognize_text", self.recognize_text),
+        ]
+    }
+
+    fn add(&mut self, other: &StageTimes) {
+        self.capture += other.capture;
+        self.prepare_input += other.prepare_input;
+        self.detect_words += other.detect_words;
+        self.find_text_lines += other.find_text_lines;
+        self.recognize_text += other.recognize_text;
+    }
+
+    fn divided(&self, frames: u32) -> StageTimes {
+        StageTimes {
+            capture: self.capture / frames,
+            prepare_input: self.prepare_input / frames,
+            detect_words: self.detect_words / frames,
+            find_text_lines: self.find_text_lines / frames,
+            recognize_text: self.recognize_text / frames,
+        }
+    }
+}
+
+// Rolling stage times: summed over `FRAME_TIMING_WINDOW` frames, then logged and kept as
+// `average` for the UI until the next window completes.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FrameTimings {
+    sum: StageTimes,
+    frames: u32,
+    pub average: Option<StageTimes>,
+}
+
+impl FrameTimings {
+    pub fn record(&mut self, times: &StageTimes) {
+        self.sum.add(times);
+        self.frames += 1;
+        if self.frames < FRAME_TIMING_WINDOW {
+            return;
+        }
+        let average = self.sum.divided(self.frames);
+        let breakdown: Vec<String> = average
+            .stages()
+            .iter()
+            .map(|(stage, time)| format!("{stage} {:.1}ms", time.as_secs_f64() * 1000.0))
+            .collect();
+        debug!("Average over {} frames: {}", self.frames, breakdown.join(", "));
+        self.average = Some(average);
+        self.sum = StageTimes::default();
+        self.frames = 0;
+    }
+}
+
+// Where one game client is in its current battle, plus what it has counted this run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowState {
+    pub title: String,
+    pub in_encounter: bool,
+    pub is_not_counted: bool,
+    pub is_shiny: bool,   // Shiny text seen for the ongoing encounter
+    pub is_trainer: bool, // The ongoing battle opened with trainer text, not a wild phrase
+    pub encounters: u32,
+    pub last_bottom: Option<StripFingerprint>, // Strip last OCR'd without finding a wild phrase
+    pub saw_text: bool, // The last OCR'd frame had any text in either crop
+    pub last_counted_at: Option<DateTime<Utc>>,
+    pub frames_without_mons: u32, // Polls since the wild phrase that found no names yet
+    pub rearm_cooldown: u32,      // Polls left before a new count can register
+    pub location: Option<String>, // Last location read while out of battle
+    pub last_location_hash: Option<u64>,
+    pub last_location_read: Option<Instant>,
+}
+
+impl WindowState {
+    pub fn new(title: String) -> Self {
+        Self {
+            title,
             in_encounter: false,
             is_not_counted: true,
-            unsaved_encounters: 0, // ✅ Initialize here
+            is_shiny: false,
+            is_trainer: false,
+            encounters: 0,
+            last_bottom: None,
+            saw_text: false,
+            last_counted_at: None,
+            frames_without_mons: 0,
+            rearm_cooldown: 0,
+            location: None,
+            last_location_hash: None,
+            last_location_read: None,
+        }
+    }
+
+    // Back to waiting for the next wild phrase.
+    fn end_battle(&mut self) {
+        self.in_encounter = false;
+        self.is_not_counted = true;
+        self.is_shiny = false;
+        self.is_trainer = false;
+    }
+}
+
+// One Start -> Pause/Quit stretch of counting.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Session {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub encounters: u32,
+    pub mons: HashMap<String, u32>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            started_at: Utc::now(),
+            ended_at: None,
+            encounters: 0,
+            mons: HashMap::new(),
+        }
+    }
+}
+
+impl Session {
+    // Time spent counting; frozen once the session is paused or quit.
+    pub fn elapsed(&self) -> chrono::Duration {
+        self.ended_at.unwrap_or_else(Utc::now) - self.started_at
+    }
+
+    pub fn encounters_per_hour(&self) -> Option<f64> {
+        let elapsed_ms = self.elapsed().num_milliseconds();
+        if elapsed_ms <= 0 {
+            return None;
+        }
+        Some(self.encounters as f64 * 3_600_000.0 / elapsed_ms as f64)
+    }
+}
+
+impl EncounterState {
+    pub fn start_session(&mut self) {
+        // A session left open by a crash is closed before starting a new one.
+        self.end_session();
+        self.sessions.push(Session::default());
+        self.playtime_mark = Some(Utc::now());
+        self.smoothed_interval_secs = None;
+    }
+
+    pub fn end_session(&mut self) {
+        self.accrue_playtime();
+        self.playtime_mark = None;
+        if let Some(session) = self.sessions.last_mut() {
+            if session.ended_at.is_none() {
+                session.ended_at = Some(Utc::now());
+            }
+        }
+    }
+
+    // Clears the session counters while keeping lifetime totals. A running session is
+    // replaced by a fresh one; otherwise an empty, already ended one is recorded.
+    pub fn reset_session(&mut self) {
+        let counting = self.open_session_mut().is_some();
+        self.end_session();
+        let mut session = Session::default();
+        if counting {
+            self.playtime_mark = Some(session.started_at);
+        } else {
+            session.ended_at = Some(session.started_at);
+        }
+        self.sessions.push(session);
+        self.smoothed_interval_secs = None;
+        // Undo must not reach back into the previous session.
+        self.last_encounter.clear();
+        self.last_window = None;
+        for win in self.windows.values_mut() {
+            win.encounters = 0;
+        }
+    }
+
+    // Moves counting time since the mark into `total_playtime_secs`. Called before every save,
+    // so after a crash only the time since the last save is lost, never counted twice.
+    pub fn accrue_playtime(&mut self) {
+        if let Some(mark) = self.playtime_mark {
+            let now = Utc::now();
+            let elapsed = (now - mark).num_seconds().max(0) as u64;
+            self.total_playtime_secs += elapsed;
+            // Keep the sub-second remainder for the next accrual.
+            self.playtime_mark = Some(mark + chrono::Duration::seconds(elapsed as i64));
+        }
+    }
+
+    // Lifetime counting time, including the running stretch not yet accrued.
+    pub fn playtime_secs(&self) -> u64 {
+        let running = self
+            .playtime_mark
+            .map_or(0, |mark| (Utc::now() - mark).num_seconds().max(0) as u64);
+        self.total_playtime_secs + running
+    }
+
+    // Extends the chain for each mon matching the chained species, restarting it at 1 otherwise.
+    fn update_chain(&mut self, mons: &[String]) {
+        for mon in mons {
+            if self.chain_species.as_ref() == Some(mon) {
+                self.current_chain += 1;
+            } else {
+                self.chain_species = Some(mon.clone());
+                self.current_chain = 1;
+            }
+        }
+    }
+
+    // Adds the battle to the phase in `unit`s, restarting at 0 when the target species is in it.
+    fn update_since_target(&mut self, mons: &[String], target: Option<&str>, unit: CountUnit) {
+        let Some(target) = target.map(str::trim).filter(|t| !t.is_empty()) else {
+            return;
+        };
+        let is_target = |mon: &String| mon.eq_ignore_ascii_case(target);
+        match unit {
+            CountUnit::Mons => {
+                for mon in mons {
+                    if is_target(mon) {
+                        self.encounters_since_target = 0;
+                    } else {
+                        self.encounters_since_target += 1;
+                    }
+                }
+            }
+            CountUnit::Battles if mons.iter().any(is_target) => self.encounters_since_target = 0,
+            CountUnit::Battles => self.encounters_since_target += 1,
+        }
+    }
+
+    // Folds the time since the previous count, per encounter, into the smoothed interval. Only
+    // counts within the running session are used, so a break doesn't drag the rate down.
+    fn update_smoothed_interval(&mut self, now: DateTime<Utc>, counted: u32, alpha: f64) {
+        let Some(previous) = self.last_encounter_time else {
+            return;
+        };
+        let in_session = self
+            .sessions
+            .last()
+            .is_some_and(|s| s.ended_at.is_none() && previous >= s.started_at);
+        if !in_session || counted == 0 {
+            return;
+        }
+        let sample = (now - previous).num_milliseconds().max(0) as f64 / 1000.0 / counted as f64;
+        self.smoothed_interval_secs = Some(match self.smoothed_interval_secs {
+            Some(average) => alpha * sample + (1.0 - alpha) * average,
+            None => sample,
+        });
+    }
+
+    // Encounters per hour from the smoothed interval. None until two counts in this session.
+    pub fn smoothed_rate(&self) -> Option<f64> {
+        self.smoothed_interval_secs
+            .filter(|secs| *secs > 0.0)
+            .map(|secs| 3600.0 / secs)
+    }
+
+    // Pokemon in `mon_stats`. Equals `encounters` unless battles are counted instead, so this
+    // is what species shares are taken of.
+    pub fn mons_seen(&self) -> u32 {
+        self.mon_stats.values().sum()
+    }
+
+    // Highest milestone the count has reached that wasn't announced yet.
+    fn reached_milestone(&self, milestones: &[u32]) -> Option<u32> {
+        milestones
+            .iter()
+            .copied()
+            .filter(|m| *m > self.last_milestone && *m <= self.encounters)
+            .max()
+    }
+
+    // Share of OCR'd frames that found a battle, in percent. None before the first frame.
+    pub fn ocr_hit_rate(&self) -> Option<f64> {
+        (self.frames_processed > 0)
+            .then(|| self.frames_with_encounter as f64 * 100.0 / self.frames_processed as f64)
+    }
+
+    // The open session, or the most recent one when paused.
+    pub fn current_session(&self) -> Option<&Session> {
+        self.sessions.last()
+    }
+
+    fn open_session_mut(&mut self) -> Option<&mut Session> {
+        self.sessions.last_mut().filter(|s| s.ended_at.is_none())
+    }
+}
+
+// Crop area of the game window, expressed as ratios of its width and height.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CaptureRegion {
+    pub start_x: f32,
+    pub end_x: f32,
+    pub start_y: f32,
+    pub end_y: f32,
+    pub debug_filename: String,
+}
+
+// Regions used for the "a wild" check, for reading the Pokemon names and for the location name.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CaptureRegions {
+    pub wild: CaptureRegion,
+    pub mons: CaptureRegion,
+    #[serde(default = "default_location_region")]
+    pub location: CaptureRegion,
+}
+
+// 0% to 30% width and 0% to 6% height, where the map name is shown.
+fn default_location_region() -> CaptureRegion {
+    CaptureRegion {
+        start_x: 0.0,
+        end_x: 0.3,
+        start_y: 0.0,
+        end_y: 0.06,
+        debug_filename: "debug_location.png".to_string(),
+    }
+}
+
+impl Default for CaptureRegions {
+    fn default() -> Self {
+        Self {
+            // 6% to 70% width and 60% to 78% height
+            wild: CaptureRegion {
+                start_x: 0.06,
+                end_x: 0.7,
+                start_y: 0.6,
+                end_y: 0.78,
+                debug_filename: "debug_bottom.png".to_string(),
+            },
+            // 6% to 94% width and 6% to 30% height
+            mons: CaptureRegion {
+                start_x: 0.06,
+                end_x: 0.94,
+                start_y: 0.06,
+                end_y: 0.3,
+                debug_filename: "debug.png".to_string(),
+            },
+            location: default_location_region(),
         }
     }
 }
 
+pub const STATE_FILE: &str = "state.json";
+// Appended to the state file's path for the in-progress write and the previous good copy.
+pub const STATE_TMP_SUFFIX: &str = ".tmp";
+pub const STATE_BACKUP_SUFFIX: &str = ".bak";
+// Set once from `--state-file`; see `state_file`.
+static STATE_PATH: OnceCell<PathBuf> = OnceCell::new();
+// Set by `--no-save`: every state file write is skipped, so testing can't touch real progress.
+static NO_SAVE: AtomicBool = AtomicBool::new(false);
+// Rolling backups of the state file, from the config: every BACKUP_EVERY saves (0 = never),
+// keeping BACKUP_KEEP. SAVES counts the saves since the last backup.
+static BACKUP_EVERY: AtomicU32 = AtomicU32::new(0);
+static BACKUP_KEEP: AtomicUsize = AtomicUsize::new(0);
+static SAVES: AtomicU32 = AtomicU32::new(0);
+
+// Folder beside the state file that the rolling backups go in.
+pub const BACKUP_DIR: &str = "backups";
+
+// Schema version written to state.json. Bump it and extend `migrate` when the format changes.
+// v0: bare `EncounterState`. v1: wrapped in `SavedState`.
+pub const STATE_VERSION: u32 = 1;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SavedState {
+    #[serde(default)]
+    pub version: u32,
     pub state: EncounterState,  // ✅ Store the actual encounter data
     pub crashed: bool,          // ✅ Track if the last session crashed
 }
 
-pub fn game_exist(w: &Window) -> bool {
-    let name = w.app_name().to_lowercase();
-    let title = w.title().to_lowercase();
-    [APP_NAME, JAVA].contains(&name.as_str()) || [APP_NAME, JAVA].contains(&title.as_str())
+// Brings any known state.json layout up to `STATE_VERSION`.
+pub fn migrate(json: serde_json::Value) -> Result<SavedState, Box<dyn Error>> {
+    let version = match json.get("version").and_then(|v| v.as_u64()) {
+        Some(version) => version as u32,
+        // v1 files written before the version field existed still have the wrapper.
+        None if json.get("state").is_some() => 1,
+        None => 0,
+    };
+    if version > STATE_VERSION {
+        return Err(format!(
+            "state.json is version {version}, this app only understands up to {STATE_VERSION}"
+        )
+        .into());
+    }
+
+    let mut saved_state = match version {
+        0 => SavedState {
+            version: 0,
+            state: serde_json::from_value(json)?,
+            crashed: false,
+        },
+        _ => serde_json::from_value(json)?,
+    };
+    saved_state.version = STATE_VERSION;
+    Ok(saved_state)
+}
+
+// Lowercased window titles/app names that identify the game client. A process id, when set,
+// takes over from the names, so blank or changing titles don't matter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowMatcher {
+    pub names: Vec<String>,
+    pub pid: Option<u32>,
+}
+
+impl Default for WindowMatcher {
+    fn default() -> Self {
+        Self {
+            names: vec![APP_NAME.to_string(), JAVA.to_string()],
+            pid: None,
+        }
+    }
+}
+
+impl WindowMatcher {
+    pub fn new(name: Option<String>, pid: Option<u32>) -> Self {
+        let mut matcher = match name {
+            Some(name) => Self {
+                names: vec![name.to_lowercase()],
+                pid: None,
+            },
+            None => Self::default(),
+        };
+        matcher.pid = pid;
+        matcher
+    }
+
+    pub fn matches(&self, w: &Window) -> bool {
+        if let Some(pid) = self.pid {
+            return w.pid() == pid;
+        }
+        let name = w.app_name().to_lowercase();
+        let title = w.title().to_lowercase();
+        self.names.iter().any(|n| *n == name || *n == title)
+    }
+
+    // What is being looked for, for "game not found" messages.
+    pub fn describe(&self) -> String {
+        match self.pid {
+            Some(pid) => format!("PID {}", pid),
+            None => self.names.join("/"),
+        }
+    }
+}
+
+pub fn game_exist(w: &Window, matcher: &WindowMatcher) -> bool {
+    matcher.matches(w)
+}
+
+// Smallest game window side, in pixels, worth capturing. Minimized windows report 0 or a few.
+pub const MIN_WINDOW_SIZE: u32 = 200;
+
+// Whether the window is big enough to crop readable text from.
+pub fn is_window_capturable(w: &Window) -> bool {
+    !w.is_minimized() && w.width() >= MIN_WINDOW_SIZE && w.height() >= MIN_WINDOW_SIZE
+}
+
+// xcap does not say which window is in the foreground, so a minimized client is treated as
+// unfocused and anything else as focused.
+pub fn window_focused(w: &Window) -> bool {
+    !w.is_minimized()
+}
+
+// Every open game client, for multi-boxing. Empty when none is found or listing fails.
+pub fn game_windows(matcher: &WindowMatcher) -> Vec<Window> {
+    try_game_windows(matcher).unwrap_or_default()
+}
+
+// Like `game_windows`, but keeps a failure to list windows (i.e. a compositor hiccup) apart
+// from there being no game window.
+pub fn try_game_windows(matcher: &WindowMatcher) -> Result<Vec<Window>, Box<dyn Error>> {
+    Ok(Window::all()?
+        .into_iter()
+        .filter(|w| game_exist(w, matcher))
+        .collect())
 }
 
 pub fn get_current_working_dir() -> (String, String) {
@@ -68,186 +715,1331 @@ pub fn get_current_working_dir() -> (String, String) {
     }
 }
 
+// `path` with `suffix` added to the file name, i.e. state.json -> state.json.bak.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn read_saved_state(path: &Path) -> Result<(SavedState, Option<u64>), Box<dyn Error>> {
+    let state_json = fs::read_to_string(path)?;
+    let json: serde_json::Value = serde_json::from_str(&state_json)?;
+    let old_version = json.get("version").and_then(|v| v.as_u64());
+    Ok((migrate(json)?, old_version))
+}
+
+pub fn set_no_save(no_save: bool) {
+    NO_SAVE.store(no_save, Ordering::SeqCst);
+}
+
+pub fn no_save() -> bool {
+    NO_SAVE.load(Ordering::SeqCst)
+}
+
+// Uses `path` for `load_state`/`save_state` from now on. Only the first call counts.
+pub fn set_state_file(path: PathBuf) {
+    if STATE_PATH.set(path).is_err() {
+        warn!("State file is already set to {}", state_file().display());
+    }
+}
+
+// Like the models: a state.json in the working directory wins, otherwise it lives next to the
+// exe, so launching from another folder doesn't start a fresh count.
+pub fn state_file() -> PathBuf {
+    STATE_PATH
+        .get_or_init(|| {
+            let (exe_path, path) = get_current_working_dir();
+            let local = Path::new(&path).join(STATE_FILE);
+            if local.is_file() {
+                local
+            } else {
+                Path::new(&exe_path).join(STATE_FILE)
+            }
+        })
+        .clone()
+}
+
 pub fn load_state() -> Result<EncounterState, Box<dyn Error>> {
-    let state_json = fs::read_to_string("state.json")?;
+    load_state_from(&state_file())
+}
 
-    // ✅ Try to load as `SavedState`
-    if let Ok(saved_state) = serde_json::from_str::<SavedState>(&state_json) {
-        if saved_state.crashed {
-            eprintln!("[WARNING] Last session did not exit cleanly. Restoring progress...");
+pub fn load_state_from(path: &Path) -> Result<EncounterState, Box<dyn Error>> {
+    let backup_path = with_suffix(path, STATE_BACKUP_SUFFIX);
+    let (saved_state, old_version, from_backup) = match read_saved_state(path) {
+        Ok((saved_state, old_version)) => (saved_state, old_version, false),
+        // A missing file just means a fresh start; anything else falls back to the backup.
+        Err(e) if path.exists() && backup_path.exists() => {
+            warn!(
+                "{} is unreadable ({e}), restoring from {}...",
+                path.display(),
+                backup_path.display()
+            );
+            let (saved_state, old_version) = read_saved_state(&backup_path)?;
+            (saved_state, old_version, true)
         }
-        return Ok(saved_state.state);
+        Err(e) => return Err(e),
+    };
+
+    if saved_state.crashed {
+        warn!("Last session did not exit cleanly. Restoring progress...");
     }
+    if old_version != Some(STATE_VERSION as u64) {
+        warn!("Updating {} to format version {STATE_VERSION}...", path.display());
+    }
+    if from_backup || old_version != Some(STATE_VERSION as u64) {
+        // The progress is already read, so a failed rewrite (i.e. read-only folder) must not
+        // lose it; the next regular save tries again.
+        if let Err(e) = save_state_to(&saved_state.state, saved_state.crashed, path) {
+            warn!("Couldn't rewrite {}: {e}", path.display());
+        }
+    }
+    Ok(saved_state.state)
+}
+
 
-    // ✅ If parsing as `SavedState` fails, try loading as `EncounterState` (old format)
-    if let Ok(old_state) = serde_json::from_str::<EncounterState>(&state_json) {
-        eprintln!("[WARNING] Detected old state format. Updating to new format...");
-        save_state(&old_state, false)?;  // ✅ Rewrite with new format
-        return Ok(old_state);
+
+
+// Peeks at the saved `debug` flag without the warnings `load_state` may log, so logging can
+// be set up before anything else runs.
+pub fn saved_debug_flag() -> bool {
+    fs::read_to_string(state_file())
+        .ok()
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+        .and_then(|value| {
+            let state = value.get("state").unwrap_or(&value);
+            state.get("debug")?.as_bool()
+        })
+        .unwrap_or(false)
+}
+
+pub fn load_regions() -> CaptureRegions {
+    let regions_json = match fs::read_to_string("regions.json") {
+        Ok(json) => json,
+        Err(_) => return CaptureRegions::default(),
+    };
+    match serde_json::from_str::<CaptureRegions>(&regions_json) {
+        Ok(regions) => sanitize_regions(regions),
+        Err(e) => {
+            warn!("Failed to parse regions.json ({e}), using default regions.");
+            CaptureRegions::default()
+        }
     }
+}
 
-    Err("Failed to parse state.json".into())  // ❌ Return error if both attempts fail
+// Fixes hand-edited regions: ratios are clamped to 0.0 - 1.0 and a start after its end is
+// swapped. A region that is still empty (or not a number) is replaced by its default. Every
+// change is logged.
+pub fn sanitize_regions(regions: CaptureRegions) -> CaptureRegions {
+    let defaults = CaptureRegions::default();
+    let fix = |region: CaptureRegion, default: CaptureRegion, name: &str| {
+        region.sanitized(name).unwrap_or_else(|| {
+            warn!("{name} region in regions.json is unusable, using the default.");
+            default
+        })
+    };
+    CaptureRegions {
+        wild: fix(regions.wild, defaults.wild, "Wild"),
+        mons: fix(regions.mons, defaults.mons, "Mons"),
+        location: fix(regions.location, defaults.location, "Location"),
+    }
 }
 
+pub fn save_regions(regions: &CaptureRegions) -> Result<(), Box<dyn Error>> {
+    fs::write("regions.json", serde_json::to_string_pretty(regions)?)?;
+    Ok(())
+}
 
+impl CaptureRegion {
+    // This region with its ratios clamped and put in order, or None when no crop is left.
+    fn sanitized(mut self, name: &str) -> Option<Self> {
+        for (axis, start, end) in [
+            ("x", &mut self.start_x, &mut self.end_x),
+            ("y", &mut self.start_y, &mut self.end_y),
+        ] {
+            if start.is_nan() || end.is_nan() {
+                return None;
+            }
+            let (clamped_start, clamped_end) = (start.clamp(0.0, 1.0), end.clamp(0.0, 1.0));
+            if (clamped_start, clamped_end) != (*start, *end) {
+                warn!("{name} region {axis} ratios {start} - {end} clamped to 0.0 - 1.0.");
+            }
+            *start = clamped_start.min(clamped_end);
+            *end = clamped_start.max(clamped_end);
+            if clamped_start > clamped_end {
+                warn!("{name} region {axis} started after it ended, swapped.");
+            }
+            if start == end {
+                return None;
+            }
+        }
+        Some(self)
+    }
 
+    // Ratios must stay inside the window and leave a non-empty crop. `name` is the region's
+    // label, already in `lang`.
+    pub fn validate(&self, name: &str, lang: Lang) -> Vec<String> {
+        let in_range = |v: f32| (0.0..=1.0).contains(&v);
+        let mut errors = vec![];
+        if ![self.start_x, self.end_x, self.start_y, self.end_y].into_iter().all(in_range) {
+            errors.push(lang.tr("{}: ratios must be between 0.0 and 1.0.").replace("{}", name));
+        }
+        if self.start_x >= self.end_x || self.start_y >= self.end_y {
+            errors.push(lang.tr("{}: must start before it ends.").replace("{}", name));
+        }
+        errors
+    }
+}
 
 pub fn save_state(state: &EncounterState, crashed: bool) -> Result<(), Box<dyn Error>> {
+    let path = state_file();
+    save_state_to(state, crashed, &path)?;
+    let every = BACKUP_EVERY.load(Ordering::SeqCst);
+    if no_save() || every == 0 || SAVES.fetch_add(1, Ordering::SeqCst) + 1 < every {
+        return Ok(());
+    }
+    SAVES.store(0, Ordering::SeqCst);
+    // The save itself worked, so a failed backup is only worth a warning.
+    if let Err(e) = backup_state(&path, BACKUP_KEEP.load(Ordering::SeqCst)) {
+        warn!("Couldn't back up {}: {e}", path.display());
+    }
+    Ok(())
+}
+
+pub fn set_backups(every: u32, keep: usize) {
+    BACKUP_EVERY.store(every, Ordering::SeqCst);
+    BACKUP_KEEP.store(keep, Ordering::SeqCst);
+}
+
+// Copies the state file at `path` to `backups/state-<timestamp>.json` beside it, then drops
+// the oldest backups beyond `keep`. Returns the new backup's path.
+pub fn backup_state(path: &Path, keep: usize) -> Result<PathBuf, Box<dyn Error>> {
+    let dir = path.parent().unwrap_or(Path::new("")).join(BACKUP_DIR);
+    fs::create_dir_all(&dir)?;
+    let stamp = Utc::now().format("%Y%m%d_%H%M%S_%3f");
+    let backup = dir.join(format!("state-{stamp}.json"));
+    fs::copy(path, &backup)?;
+
+    // The timestamp makes name order the age order.
+    let mut backups: Vec<_> = fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("state-") && name.ends_with(".json"))
+        })
+        .collect();
+    if backups.len() > keep.max(1) {
+        backups.sort();
+        for old in &backups[..backups.len() - keep.max(1)] {
+            if let Err(e) = fs::remove_file(old) {
+                warn!("Failed to remove old backup {}: {e}", old.display());
+            }
+        }
+    }
+    Ok(backup)
+}
+
+pub fn save_state_to(
+    state: &EncounterState,
+    crashed: bool,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if no_save() {
+        info!("Save skipped (no-save mode).");
+        return Ok(());
+    }
     let saved_state = SavedState {
+        version: STATE_VERSION,
         state: state.clone(),
         crashed,  // ✅ Allow specifying whether it's a crash or normal save
     };
 
+    // Write a temp file and rename it over the real one, so a kill mid-write can't leave a
+    // truncated state.json. The previous good file is kept as the backup.
     let state_json = serde_json::to_string(&saved_state)?;
-    fs::write("state.json", state_json)?;
+    let tmp_path = with_suffix(path, STATE_TMP_SUFFIX);
+    fs::write(&tmp_path, state_json)?;
+    if path.exists() {
+        fs::copy(path, with_suffix(path, STATE_BACKUP_SUFFIX))?;
+    }
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+
+fn remove_mons(stats: &mut HashMap<String, u32>, mons: &[String]) {
+    for mon in mons {
+        if let Some(count) = stats.get_mut(mon) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                stats.remove(mon);
+            }
+        }
+    }
+}
+
+// Reverts the most recent count, which `unit` says how much it added, and steps back to the
+// one before it in `recent_encounters`, so repeated undos take back one encounter at a time.
+// Stops at the start of the session. Returns false when there is nothing to undo.
+pub fn undo_last_encounter(state: &mut EncounterState, unit: CountUnit) -> bool {
+    if state.last_encounter.is_empty() {
+        return false;
+    }
+    let mons = std::mem::take(&mut state.last_encounter);
+    state.last_encounter_time = None;
+    if let Some(recent) = state.recent_encounters.pop_back() {
+        if let Some(stats) = recent
+            .location
+            .and_then(|location| state.location_stats.get_mut(&location))
+        {
+            remove_mons(stats, &mons);
+        }
+    }
+    let counted = unit.increment(mons.len());
+    state.encounters = state.encounters.saturating_sub(counted);
+    remove_mons(&mut state.mon_stats, &mons);
+    if let Some(win) = state
+        .last_window
+        .take()
+        .and_then(|id| state.windows.get_mut(&id))
+    {
+        win.encounters = win.encounters.saturating_sub(counted);
+        // A misdetection should not hold back the next real encounter.
+        win.last_counted_at = None;
+    }
+    if let Some(session) = state.sessions.last_mut() {
+        session.encounters = session.encounters.saturating_sub(counted);
+        remove_mons(&mut session.mons, &mons);
+    }
+    let session_start = state.current_session().map(|session| session.started_at);
+    if let Some(previous) = state
+        .recent_encounters
+        .back()
+        .filter(|previous| session_start.is_none_or(|start| previous.at >= start))
+    {
+        state.last_encounter = previous.mons.clone();
+        state.last_encounter_time = Some(previous.at);
+        state.last_window = previous.window;
+    }
+    true
+}
+
+// What `consolidate_stats` changed, for the UI.
+#[derive(Debug, Default, PartialEq)]
+pub struct StatsCleanup {
+    pub species_before: usize,
+    pub species_after: usize,
+    pub renamed: Vec<(String, String)>, // Old `mon_stats` key -> name its count went into
+}
+
+// Stats key a misread name belongs under: the closest species within `max_distance` edits, or
+// else the name lowercased without stray punctuation around it.
+fn canonical_name(name: &str, max_distance: usize) -> String {
+    let cleaned = name
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase();
+    if cleaned.is_empty() {
+        return name.to_string();
+    }
+    species::normalize_mon_name(name, max_distance)
+        .or_else(|| species::normalize_mon_name(&cleaned, max_distance))
+        .unwrap_or(cleaned)
+}
+
+// Re-keys `counts` by canonical name, adding up counts that land on the same one.
+fn merge_names(counts: &mut HashMap<String, u32>, max_distance: usize) -> Vec<(String, String)> {
+    let mut renamed = vec![];
+    for (name, count) in std::mem::take(counts) {
+        let canonical = canonical_name(&name, max_distance);
+        if canonical != name {
+            renamed.push((name, canonical.clone()));
+        }
+        *counts.entry(canonical).or_insert(0) += count;
+    }
+    renamed.sort();
+    renamed
+}
+
+// Merges OCR near-duplicates ("pidqey", "pidgey.") into the species they were meant to be.
+// Every place that stores names is rewritten, so undo still finds what it needs to remove.
+pub fn consolidate_stats(state: &mut EncounterState, max_distance: usize) -> StatsCleanup {
+    let species_before = state.mon_stats.len();
+    let renamed = merge_names(&mut state.mon_stats, max_distance);
+    for counts in state.location_stats.values_mut() {
+        merge_names(counts, max_distance);
+    }
+    for session in &mut state.sessions {
+        merge_names(&mut session.mons, max_distance);
+    }
+    for (name, levels) in std::mem::take(&mut state.level_stats) {
+        let canonical = canonical_name(&name, max_distance);
+        state.level_stats.entry(canonical).or_default().merge(levels);
+    }
+    let rename = |names: &mut Vec<String>| {
+        for name in names.iter_mut() {
+            *name = canonical_name(name, max_distance);
+        }
+    };
+    rename(&mut state.last_encounter);
+    rename(&mut state.shiny_mons);
+    for recent in &mut state.recent_encounters {
+        rename(&mut recent.mons);
+    }
+    if let Some(species) = &mut state.chain_species {
+        *species = canonical_name(species, max_distance);
+    }
+    StatsCleanup {
+        species_before,
+        species_after: state.mon_stats.len(),
+        renamed,
+    }
+}
+
+// Adds every count in `from` to `into`.
+fn add_counts(into: &mut HashMap<String, u32>, from: HashMap<String, u32>) {
+    for (name, count) in from {
+        *into.entry(name).or_insert(0) += count;
+    }
+}
+
+// Progress from two machines as one: counts are added up, sessions and the history are
+// interleaved by time. The chain, the since-target count and the last encounter come from
+// whichever state counted last, since only its side knows what came right before.
+pub fn merge_states(a: EncounterState, b: EncounterState) -> EncounterState {
+    let (older, newer) = if a.last_encounter_time > b.last_encounter_time {
+        (b, a)
+    } else {
+        (a, b)
+    };
+    let mut shiny_mons = older.shiny_mons;
+    shiny_mons.extend(newer.shiny_mons);
+    let mut merged = EncounterState {
+        encounters: older.encounters + newer.encounters,
+        shiny_encounters: older.shiny_encounters + newer.shiny_encounters,
+        shiny_mons,
+        total_playtime_secs: older.total_playtime_secs + newer.total_playtime_secs,
+        last_milestone: older.last_milestone.max(newer.last_milestone),
+        debug: older.debug || newer.debug,
+        unsaved_encounters: 0,
+        ..newer
+    };
+    add_counts(&mut merged.mon_stats, older.mon_stats);
+    for (location, counts) in older.location_stats {
+        add_counts(merged.location_stats.entry(location).or_default(), counts);
+    }
+    for (mon, levels) in older.level_stats {
+        merged.level_stats.entry(mon).or_default().merge(levels);
+    }
+    merged.sessions.extend(older.sessions);
+    merged.sessions.sort_by_key(|session| session.started_at);
+    merged.recent_encounters.extend(older.recent_encounters);
+    merged.recent_encounters.make_contiguous().sort_by_key(|recent| recent.at);
+    merged
+}
+
+// One line of the encounter event log.
+#[derive(Debug, Serialize)]
+struct EncounterEvent<'a> {
+    ts: DateTime<Utc>,
+    mons: &'a [String],
+    shiny: bool,
+}
+
+// Appends one encounter to the JSON-lines log. The file is opened per event, so a log that
+// was rotated or deleted is simply recreated.
+pub fn append_encounter_event(
+    path: &Path,
+    mons: &[String],
+    shiny: bool,
+) -> Result<(), Box<dyn Error>> {
+    let event = EncounterEvent {
+        ts: Utc::now(),
+        mons,
+        shiny,
+    };
+    let line = format!("{}\n", serde_json::to_string(&event)?);
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+// Writes `mon_stats` as `name,count` rows, most encountered first.
+pub fn export_csv(state: &EncounterState, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut stats: Vec<(&String, &u32)> = state.mon_stats.iter().collect();
+    stats.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    let mut csv = String::from("name,count\n");
+    for (mon, count) in stats {
+        let name = if mon.contains(',') || mon.contains('"') {
+            format!("\"{}\"", mon.replace('"', "\"\""))
+        } else {
+            mon.clone()
+        };
+        csv.push_str(&format!("{},{}\n", name, count));
+    }
+    fs::write(path, csv)?;
+    Ok(())
+}
+
+// Locks `mutex`, taking the data back if a thread panicked while holding it. The counts are still
+// usable, and unwrapping would take every other thread down with the one that panicked.
+pub fn lock_or_recover<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        warn!("A thread panicked while holding a lock; continuing with its data.");
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+// Ends the session and saves, giving up after `timeout` so shutdown never hangs on a lock the
+// worker (or a panicking thread) still holds. `crashed` is recorded for the next start.
+pub fn flush_state(
+    state: &Arc<Mutex<EncounterState>>,
+    timeout: Duration,
+    crashed: bool,
+) -> Result<(), String> {
+    save_with_timeout(state, timeout, crashed, true)
+}
+
+// Saves from the panic hook. A panic on another thread may leave the app running, so the
+// session stays open and counting carries on; only the playtime so far is banked.
+pub fn crash_save(state: &Arc<Mutex<EncounterState>>, timeout: Duration) -> Result<(), String> {
+    save_with_timeout(state, timeout, true, false)
+}
+
+fn save_with_timeout(
+    state: &Arc<Mutex<EncounterState>>,
+    timeout: Duration,
+    crashed: bool,
+    end_session: bool,
+) -> Result<(), String> {
+    let state = Arc::clone(state);
+    let (done_tx, done_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut state = lock_or_recover(&state);
+        if end_session {
+            state.end_session();
+        } else {
+            state.accrue_playtime();
+        }
+        let result = if state.dry_run {
+            Ok(())
+        } else {
+            save_state(&state, crashed).map_err(|e| e.to_string())
+        };
+        let _ = done_tx.send(result);
+    });
+    done_rx
+        .recv_timeout(timeout)
+        .unwrap_or_else(|_| Err(format!("saving took longer than {}ms", timeout.as_millis())))
+}
+
+// Writes the whole state in the state.json format, for moving progress to another machine.
+pub fn export_state(state: &EncounterState, path: &Path) -> Result<(), Box<dyn Error>> {
+    let saved_state = SavedState {
+        version: STATE_VERSION,
+        state: state.clone(),
+        crashed: false,
+    };
+    fs::write(path, serde_json::to_string_pretty(&saved_state)?)?;
     Ok(())
 }
 
+// Reads a file written by `export_state`, or a state.json of any known version. Nothing is
+// changed here, so a malformed file leaves the current state alone.
+pub fn import_state(path: &Path) -> Result<EncounterState, Box<dyn Error>> {
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let state = migrate(json)?.state;
+    // Counting battles, each encounter can hold up to a horde's worth of Pokemon.
+    let counted = state.mons_seen();
+    if counted > state.encounters.saturating_mul(HORDE_SIZE as u32) {
+        return Err(format!(
+            "{} has {counted} Pokemon in its stats but only {} encounters",
+            path.display(),
+            state.encounters
+        )
+        .into());
+    }
+    Ok(state)
+}
+
+// Sleep before the next poll after `failures` errors in a row: doubles from 100ms, capped at 3.2s.
+pub fn capture_backoff(failures: u32) -> u64 {
+    100 << failures.saturating_sub(1).min(5)
+}
+
+// Smallest crop side, in pixels, that is still worth running OCR on.
+pub const MIN_CROP_SIZE: u32 = 16;
+
+// Pixel rectangle (x, y, width, height) of `region` in a window of the given size, clamped to
+// the window. Errors when the window is too small (i.e. resized down) to hold a usable crop.
+pub fn crop_bounds(
+    width: u32,
+    height: u32,
+    region: &CaptureRegion,
+) -> Result<(u32, u32, u32, u32), Box<dyn Error>> {
+    let scale = |ratio: f32, size: u32| ((size as f32 * ratio) as u32).min(size);
+    let start_x = scale(region.start_x, width);
+    let end_x = scale(region.end_x, width);
+    let start_y = scale(region.start_y, height);
+    let end_y = scale(region.end_y, height);
+
+    let crop_width = end_x.saturating_sub(start_x);
+    let crop_height = end_y.saturating_sub(start_y);
+    if crop_width < MIN_CROP_SIZE || crop_height < MIN_CROP_SIZE {
+        return Err(format!(
+            "window {width}x{height} is too small to crop {} ({crop_width}x{crop_height})",
+            region.debug_filename
+        )
+        .into());
+    }
+    Ok((start_x, start_y, crop_width, crop_height))
+}
+
+// Color handling applied to a crop before OCR: grayscale and/or a luminance threshold that
+// turns every pixel black or white.
+pub fn prepare_crop(img: DynamicImage, config: &Config) -> RgbImage {
+    if let Some(threshold) = config.threshold {
+        let mut luma = img.to_luma8();
+        for pixel in luma.pixels_mut() {
+            pixel.0[0] = if pixel.0[0] >= threshold { 255 } else { 0 };
+        }
+        return DynamicImage::ImageLuma8(luma).to_rgb8();
+    }
+    if config.grayscale {
+        img.grayscale().to_rgb8()
+    } else {
+        img.to_rgb8()
+    }
+}
 
 fn capture_crop(
     debug: bool,
-    window: &Window,
-    start_x_ratio: f32,
-    end_x_ratio: f32,
-    start_y_ratio: f32,
-    end_y_ratio: f32,
-    debug_filename: &str,
+    frame: &RgbaImage,
+    region: &CaptureRegion,
+    config: &Config,
 ) -> Result<RgbImage, Box<dyn Error>> {
-    let screen_height = window.height();
-    let screen_width = window.width();
-
-    let start_x = (screen_width as f32 * start_x_ratio) as u32;
-    let end_x = (screen_width as f32 * end_x_ratio) as u32;
-    let start_y = (screen_height as f32 * start_y_ratio) as u32;
-    let end_y = (screen_height as f32 * end_y_ratio) as u32;
-
-    let img = window.capture_image()?;
-    let img = DynamicImage::ImageRgba8(img)
-        .crop(start_x, start_y, end_x - start_x, end_y - start_y)
-        .grayscale()
-        .to_rgb8();
+    // The window may have been resized since it was listed, so size the crop off the frame.
+    let (x, y, crop_width, crop_height) = crop_bounds(frame.width(), frame.height(), region)?;
+    let crop = image::imageops::crop_imm(frame, x, y, crop_width, crop_height).to_image();
+    let img = prepare_crop(DynamicImage::ImageRgba8(crop), config);
 
     if debug {
-        img.save(debug_filename)?;
+        match &config.debug_dir {
+            Some(dir) if !dir.is_empty() => {
+                save_debug_sequence(&img, Path::new(dir), region, config)?
+            }
+            _ => {
+                let format = config.debug_image_format();
+                let path = debug_path(Path::new(""), &region.debug_filename, format);
+                img.save_with_format(path, format)?
+            }
+        }
     }
     Ok(img)
 }
 
-fn capture_bottom(debug: bool, window: &Window) -> Result<RgbImage, Box<dyn Error>> {
-    // Crop parameters: 6% to 70% width and 60% to 78% height
-    capture_crop(debug, window, 0.06, 0.7, 0.6, 0.78, "debug_bottom.png")
+// `name` in `dir`, with the extension swapped for the debug image format's.
+fn debug_path(dir: &Path, name: &str, format: ImageFormat) -> PathBuf {
+    let extension = format.extensions_str().first().copied().unwrap_or("png");
+    dir.join(name).with_extension(extension)
+}
+
+// Writes a debug capture as `<timestamp>_<debug_filename>` in `dir`, then drops the oldest files
+// beyond `debug_keep`. The timestamp prefix makes name order the capture order.
+fn save_debug_sequence(
+    img: &RgbImage,
+    dir: &Path,
+    region: &CaptureRegion,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let stamp = Utc::now().format("%Y%m%d_%H%M%S_%3f");
+    let format = config.debug_image_format();
+    let name = format!("{stamp}_{}", region.debug_filename);
+    img.save_with_format(debug_path(dir, &name, format), format)?;
+
+    let mut files: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| ImageFormat::from_path(path).is_ok())
+        .collect();
+    if files.len() > config.debug_keep {
+        files.sort();
+        for path in &files[..files.len() - config.debug_keep] {
+            if let Err(e) = fs::remove_file(path) {
+                warn!("Failed to remove old debug capture {}: {e}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn capture_bottom(
+    debug: bool,
+    frame: &RgbaImage,
+    regions: &CaptureRegions,
+    config: &Config,
+) -> Result<RgbImage, Box<dyn Error>> {
+    capture_crop(debug, frame, &regions.wild, config)
+}
+
+fn capture_screen(
+    debug: bool,
+    frame: &RgbaImage,
+    regions: &CaptureRegions,
+    config: &Config,
+) -> Result<RgbImage, Box<dyn Error>> {
+    capture_crop(debug, frame, &regions.mons, config)
+}
+
+fn capture_location(
+    debug: bool,
+    frame: &RgbaImage,
+    regions: &CaptureRegions,
+    config: &Config,
+) -> Result<RgbImage, Box<dyn Error>> {
+    capture_crop(debug, frame, &regions.location, config)
+}
+
+// The wild and mons crops as OCR would see them, for checking the regions line up.
+pub fn capture_preview(
+    source: &dyn FrameSource,
+    regions: &CaptureRegions,
+    config: &Config,
+) -> Result<(RgbImage, RgbImage), Box<dyn Error>> {
+    preview_crops(&source.capture()?, regions, config)
+}
+
+// `capture_preview` for a frame that is already captured, so callers that also need the full
+// frame don't pay for a second capture.
+pub fn preview_crops(
+    frame: &RgbaImage,
+    regions: &CaptureRegions,
+    config: &Config,
+) -> Result<(RgbImage, RgbImage), Box<dyn Error>> {
+    Ok((
+        capture_bottom(false, frame, regions, config)?,
+        capture_screen(false, frame, regions, config)?,
+    ))
+}
+
+// Cheap fingerprint of a frame, so an unchanged strip can skip OCR.
+fn frame_hash(img: &RgbImage) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    img.as_raw().hash(&mut hasher);
+    hasher.finish()
+}
+
+// Size of the grayscale thumbnail `motion_threshold` compares. Wide, like the strip itself.
+const THUMBNAIL_WIDTH: u32 = 32;
+const THUMBNAIL_HEIGHT: u32 = 8;
+
+// What the bottom strip looked like: its exact hash, and a thumbnail in which a dialog box
+// appearing moves some cells a lot, while animated tiles and noise only move them a little.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StripFingerprint {
+    hash: u64,
+    thumbnail: Vec<u8>,
+}
+
+impl StripFingerprint {
+    pub fn new(img: &RgbImage) -> Self {
+        let gray = image::imageops::grayscale(img);
+        let thumbnail = image::imageops::resize(
+            &gray,
+            THUMBNAIL_WIDTH,
+            THUMBNAIL_HEIGHT,
+            image::imageops::FilterType::Triangle,
+        );
+        Self {
+            hash: frame_hash(img),
+            thumbnail: thumbnail.into_raw(),
+        }
+    }
+
+    // Identical, or no thumbnail cell moved more than `threshold` (0-255). 0 only accepts
+    // identical strips.
+    pub fn unchanged_from(&self, other: &Self, threshold: u8) -> bool {
+        self.hash == other.hash || (threshold > 0 && self.largest_change(other) <= threshold)
+    }
+
+    fn largest_change(&self, other: &Self) -> u8 {
+        self.thumbnail
+            .iter()
+            .zip(&other.thumbnail)
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0)
+    }
 }
 
-fn capture_screen(debug: bool, window: &Window) -> Result<RgbImage, Box<dyn Error>> {
-    // Crop parameters: 6% to 94% width and 6% to 30% height
-    capture_crop(debug, window, 0.06, 0.94, 0.06, 0.3, "debug.png")
+// ocrs does not report recognition scores, so a line is scored by the share of its
+// characters that can appear in battle text. Garbage reads are mostly stray symbols.
+fn text_confidence(text: &str) -> f32 {
+    let total = text.chars().count();
+    if total == 0 {
+        return 0.0;
+    }
+    let plausible = text
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace() || ".,!?'-".contains(*c))
+        .count();
+    plausible as f32 / total as f32
 }
 
 fn perform_ocr_lines(
     engine: &OcrEngine,
     data: RgbImage,
-) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    times: &mut StageTimes,
+) -> Result<Vec<(String, f32)>, Box<dyn Error>> {
     let small_img = DynamicImage::ImageRgb8(data).to_rgb8();
     let img = ImageSource::from_bytes(small_img.as_raw(), small_img.dimensions())?;
+    let mut stage = Instant::now();
+    let mut lap = |time: &mut Duration| {
+        *time += stage.elapsed();
+        stage = Instant::now();
+    };
     let ocr_input = engine.prepare_input(img)?;
+    lap(&mut times.prepare_input);
     let word_rects = engine.detect_words(&ocr_input)?;
+    lap(&mut times.detect_words);
     let line_rects = engine.find_text_lines(&ocr_input, &word_rects);
+    lap(&mut times.find_text_lines);
     let line_texts = engine.recognize_text(&ocr_input, &line_rects)?;
-    // Convert Vec<Option<TextLine>> into Vec<Vec<String>>
-    let converted: Vec<Vec<String>> = line_texts
+    lap(&mut times.recognize_text);
+    // Convert Vec<Option<TextLine>> into (text, confidence) pairs
+    let converted: Vec<(String, f32)> = line_texts
         .into_iter()
-        .map(|opt_line| {
-            if let Some(line) = opt_line {
-                vec![line.to_string()]
-            } else {
-                vec![]
-            }
+        .flatten()
+        .map(|line| {
+            let text = line.to_string();
+            let confidence = text_confidence(&text);
+            (text, confidence)
         })
         .collect();
     Ok(converted)
 }
 
-pub fn get_wild(engine: &OcrEngine, data: RgbImage) -> Result<bool, Box<dyn Error>> {
-    let line_texts = perform_ocr_lines(engine, data)?;
-    // Parallel iteration for faster processing
-    let contains_wild = line_texts
-        .par_iter()
-        .flatten()
-        .map(|line| line.to_string().to_lowercase())
-        .any(|line| line.contains("a wild"));
-    Ok(contains_wild)
+// Lowercased OCR lines that meet the confidence threshold.
+pub fn confident_lines(
+    engine: &OcrEngine,
+    data: RgbImage,
+    min_confidence: f32,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(timed_confident_lines(engine, data, min_confidence)?.0)
 }
 
-fn get_mons(engine: &OcrEngine, data: RgbImage) -> Result<Vec<String>, Box<dyn Error>> {
-    let line_texts = perform_ocr_lines(engine, data)?;
-    // Parallel iterator to process text lines faster
-    let mons: Vec<String> = line_texts
-        .par_iter()
-        .flatten()
-        .map(|l| l.to_string().to_lowercase())
-        .filter(|line| line.contains("lv.") || line.contains("nv.") || line.contains("niv."))
+// `confident_lines` plus the time each OCR stage took.
+fn timed_confident_lines(
+    engine: &OcrEngine,
+    data: RgbImage,
+    min_confidence: f32,
+) -> Result<(Vec<String>, StageTimes), Box<dyn Error>> {
+    let mut times = StageTimes::default();
+    let lines = perform_ocr_lines(engine, data, &mut times)?
+        .into_iter()
+        .filter(|(_, confidence)| *confidence >= min_confidence)
+        .map(|(text, _)| text.to_lowercase())
+        .collect();
+    Ok((lines, times))
+}
+
+// Pool the two crops are read on, built on first use. None when it can't be built, in which
+// case the crops are read one after the other.
+fn ocr_pool() -> Option<&'static ThreadPool> {
+    static POOL: OnceCell<Option<ThreadPool>> = OnceCell::new();
+    POOL.get_or_init(|| {
+        ThreadPoolBuilder::new()
+            .num_threads(OCR_JOIN_THREADS)
+            .thread_name(|i| format!("ocr-join-{i}"))
+            .build()
+            .map_err(|e| warn!("Couldn't start the OCR thread pool, reading crops in turn: {e}"))
+            .ok()
+    })
+    .as_ref()
+}
+
+// Runs both reads, at the same time when `parallel` is on and the pool is up.
+fn join_reads<A, B, RA, RB>(parallel: bool, a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    match parallel.then(ocr_pool).flatten() {
+        Some(pool) => pool.join(a, b),
+        None => (a(), b()),
+    }
+}
+
+// The bottom strip is read once and checked against each kind of battle message: the wild,
+// trainer and battle end phrases from the config, i.e. "a wild" or "got away safely". These scan
+// a handful of short lines, which is quicker done in place than handed out to threads. Blank
+// phrases are skipped, since they would match every line.
+pub fn contains_any_phrase(line_texts: &[String], phrases: &[String]) -> bool {
+    line_texts.iter().any(|line| {
+        phrases
+            .iter()
+            .any(|phrase| !phrase.trim().is_empty() && line.contains(&phrase.to_lowercase()))
+    })
+}
+
+pub fn has_shiny(line_texts: &[String]) -> bool {
+    line_texts
+        .iter()
+        .any(|line| SHINY_MARKERS.iter().any(|marker| line.contains(marker)))
+}
+
+// Map name from the location crop, i.e. "route 1". None when nothing readable is there.
+pub fn get_location(
+    engine: &OcrEngine,
+    data: RgbImage,
+    min_confidence: f32,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let location = confident_lines(engine, data, min_confidence)?
+        .into_iter()
+        .map(|line| line.trim().to_string())
+        .find(|line| line.chars().any(char::is_alphabetic));
+    Ok(location)
+}
+
+// Pokemon names from OCR lines: the words right before a level marker, snapped to a known
+// species. Up to `max_name_tokens` words are tried, so "mr. mime" and names OCR split in two
+// ("pid gey") are read whole.
+pub fn parse_mons(line_texts: &[String], config: &Config) -> Vec<String> {
+    parse_mon_levels(line_texts, config)
+        .into_iter()
+        .map(|(mon, _)| mon)
+        .collect()
+}
+
+// `parse_mons` with the level after each marker, written apart ("lv. 5") or joined ("lv.23").
+// None when OCR didn't read a number there.
+pub fn parse_mon_levels(line_texts: &[String], config: &Config) -> Vec<(String, Option<u32>)> {
+    let level_markers: Vec<String> =
+        config.level_markers.iter().map(|m| m.to_lowercase()).collect();
+    // What follows the marker in the token: "" for "lv.", "23" for "lv.23".
+    let after_marker = |token: &str| -> Option<String> {
+        level_markers
+            .iter()
+            .filter(|marker| !marker.is_empty())
+            .filter_map(|marker| token.strip_prefix(marker.as_str()))
+            .find(|rest| rest.is_empty() || rest.starts_with(|c: char| c.is_ascii_digit()))
+            .map(str::to_string)
+    };
+    let is_marker = |token: &str| after_marker(token).is_some();
+    line_texts
+        .iter()
+        .filter(|line| level_markers.iter().any(|marker| line.contains(marker.as_str())))
         .flat_map(|line| {
-            line.split_whitespace()
-                .collect::<Vec<_>>()
-                .windows(2)
-                .filter_map(|w| {
-                    if (w[1] == "lv." || w[1] == "nv." || w[1] == "niv.") && w[0].len() > 1 {
-                        Some(w[0].to_string())
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            tokens
+                .iter()
+                .enumerate()
+                .filter_map(|(end, token)| Some((end, after_marker(token)?)))
+                .filter_map(|(end, rest)| {
+                    let name = name_before(
+                        &tokens[..end],
+                        config.max_name_tokens,
+                        config.name_match_distance,
+                        &is_marker,
+                    )?;
+                    let level = if rest.is_empty() {
+                        tokens.get(end + 1).and_then(|next| leading_number(next))
                     } else {
-                        None
-                    }
+                        leading_number(&rest)
+                    };
+                    Some((name, level))
                 })
-                .collect::<Vec<String>>()
+                .collect::<Vec<_>>()
         })
-        .collect();
-    Ok(mons)
+        .collect()
+}
+
+// The digits `text` starts with, i.e. 5 from "5," that OCR read off a level.
+fn leading_number(text: &str) -> Option<u32> {
+    let digits: String = text.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+// The species named by the last words of `tokens`. The closest match wins, fewer words on a tie.
+// A level marker or a word without letters (the previous Pokemon's level) ends the name.
+fn name_before(
+    tokens: &[&str],
+    max_tokens: usize,
+    name_match_distance: usize,
+    is_marker: &dyn Fn(&str) -> bool,
+) -> Option<String> {
+    let name_tokens = tokens
+        .iter()
+        .rev()
+        .take(max_tokens.max(1))
+        .take_while(|token| !is_marker(token) && token.chars().any(char::is_alphabetic))
+        .count();
+    (1..=name_tokens)
+        .filter_map(|count| {
+            let raw = tokens[tokens.len() - count..].join(" ");
+            if raw.len() <= 1 {
+                return None;
+            }
+            species::closest_species(&raw, name_match_distance)
+        })
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(species, _)| species)
 }
 
+// Capture and OCR for one poll of one game window; the counting itself is `count_from_texts`.
 pub fn encounter_process(
     engine: &OcrEngine,
     state: &mut EncounterState,
-    window: &Window,
+    source: &dyn FrameSource,
+    regions: &CaptureRegions,
+    config: &Config,
 ) -> Result<bool, Box<dyn Error>> {  // ✅ Change return type to `bool`
-    let mut encounter_detected = false;
+    let id = source.id();
+    let win = state
+        .windows
+        .entry(id)
+        .or_insert_with(|| WindowState::new(source.name()));
+    let in_encounter = win.in_encounter;
+    let last_bottom = win.last_bottom.clone();
+    let saw_text = win.saw_text;
+
+    // One frame per poll, so both crops come from the same moment.
+    let capture_start = Instant::now();
+    let frame = source.capture()?;
+    let mut times = StageTimes {
+        capture: capture_start.elapsed(),
+        ..Default::default()
+    };
+    let now = Utc::now();
+    state.last_window_at = Some(now);
+    let read_lines = |img: RgbImage| {
+        timed_confident_lines(engine, img, config.min_confidence).map_err(|e| e.to_string())
+    };
+
+    let watch_battle_end = config.battle_end_phrases.iter().any(|p| !p.trim().is_empty());
+    let (wild_lines, mon_lines, bottom) = if in_encounter && watch_battle_end {
+        // The strip is read in battle too, for the message that ends it.
+        let cropped_wild = capture_bottom(state.debug, &frame, regions, config)?;
+        let cropped_image = capture_screen(state.debug, &frame, regions, config)?;
+        let (wild_lines, mon_lines) = join_reads(
+            config.use_parallel,
+            || read_lines(cropped_wild),
+            || read_lines(cropped_image),
+        );
+        (Some(wild_lines?), mon_lines?, None)
+    } else if in_encounter {
+        let cropped_image = capture_screen(state.debug, &frame, regions, config)?;
+        (None, read_lines(cropped_image)?, None)
+    } else {
+        let cropped_wild = capture_bottom(state.debug, &frame, regions, config)?;
+        // Same pixels as the last strip that had no wild phrase (or close enough, with
+        // `motion_threshold`), so OCR would say no again.
+        let bottom = StripFingerprint::new(&cropped_wild);
+        let threshold = config.motion_threshold;
+        if last_bottom.is_some_and(|last| bottom.unchanged_from(&last, threshold)) {
+            if saw_text {
+                state.last_text_at = Some(now);
+            }
+            return Ok(false);
+        }
+        // The strip changed, so this may be the transition frame: read both crops at once.
+        // Errors become Strings because Box<dyn Error> can't cross rayon threads.
+        let cropped_image = capture_screen(state.debug, &frame, regions, config)?;
+        let (wild_lines, mon_lines) = join_reads(
+            config.use_parallel,
+            || read_lines(cropped_wild),
+            || read_lines(cropped_image),
+        );
+        (Some(wild_lines?), mon_lines?, Some(bottom))
+    };
+    let wild_lines = wild_lines.map(|(lines, ocr_times)| {
+        times.add(&ocr_times);
+        lines
+    });
+    let (mon_lines, ocr_times) = mon_lines;
+    times.add(&ocr_times);
+    if state.debug {
+        state.frame_timings.record(&times);
+    }
+
+    let saw_text = !mon_lines.is_empty() || wild_lines.as_ref().is_some_and(|l| !l.is_empty());
+    if saw_text {
+        state.last_text_at = Some(now);
+    }
+    if let Some(win) = state.windows.get_mut(&id) {
+        win.saw_text = saw_text;
+    }
+    let counted = count_from_texts(state, id, wild_lines.as_deref(), &mon_lines, config)?;
+    state.frames_processed += 1;
+    if state.windows.get(&id).is_some_and(|win| win.in_encounter) {
+        state.frames_with_encounter += 1;
+    }
+    if let (Some(bottom), Some(win)) = (bottom, state.windows.get_mut(&id)) {
+        win.last_bottom = if win.in_encounter { None } else { Some(bottom) };
+    }
+    update_location(engine, state, id, &frame, regions, config)?;
+    Ok(counted)
+}
+
+// Re-reads the map name while out of battle, when its crop changed and the last read is stale.
+fn update_location(
+    engine: &OcrEngine,
+    state: &mut EncounterState,
+    id: u32,
+    frame: &RgbaImage,
+    regions: &CaptureRegions,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let debug = state.debug;
+    let Some(win) = state.windows.get_mut(&id) else {
+        return Ok(());
+    };
+    let stale = win
+        .last_location_read
+        .is_none_or(|read_at| read_at.elapsed() >= LOCATION_READ_INTERVAL);
+    if win.in_encounter || !stale {
+        return Ok(());
+    }
+    let cropped_location = capture_location(debug, frame, regions, config)?;
+    let hash = frame_hash(&cropped_location);
+    if win.last_location_hash == Some(hash) {
+        return Ok(());
+    }
+    win.last_location_hash = Some(hash);
+    win.last_location_read = Some(Instant::now());
+    if let Some(location) = get_location(engine, cropped_location, config.min_confidence)? {
+        if win.location.as_ref() != Some(&location) {
+            debug!("Location is now {location}.");
+        }
+        win.location = Some(location.clone());
+        state.current_location = Some(location);
+    }
+    Ok(())
+}
+
+// Counts one encounter of `found` (names, with the level read after each if any): totals,
+// stats, chain, session, history, notifications and the periodic save. Shared by detection
+// and the UI's manual add, which has no game window behind it.
+pub fn record_encounter(
+    state: &mut EncounterState,
+    found: &[(String, Option<u32>)],
+    location: Option<String>,
+    shiny: bool,
+    now: DateTime<Utc>,
+    config: &Config,
+) -> Result<u32, Box<dyn Error>> {
+    let mons: Vec<String> = found.iter().map(|(mon, _)| mon.clone()).collect();
+    let count_unit = config.effective_count_unit();
+    let counted = count_unit.increment(mons.len());
+    state.encounters += counted;
+    state.last_encounter = mons.clone();
+    state.update_smoothed_interval(now, counted, config.rate_smoothing_factor());
+    state.last_encounter_time = Some(now);
+    state.recent_encounters.push_back(RecentEncounter {
+        at: now,
+        mons: mons.clone(),
+        location: location.clone(),
+        window: None,
+    });
+    while state.recent_encounters.len() > config.recent_encounters_len {
+        state.recent_encounters.pop_front();
+    }
+    state.update_chain(&mons);
+    state.update_since_target(&mons, config.target_species.as_deref(), count_unit);
+    if shiny {
+        debug!("Shiny is detected.");
+        state.shiny_encounters += 1;
+        state.shiny_mons.extend(mons.iter().cloned());
+    }
+    if let Some(session) = state.open_session_mut() {
+        session.encounters += counted;
+        for mon in &mons {
+            *session.mons.entry(mon.clone()).or_insert(0) += 1;
+        }
+    }
+    notify::send_webhook(config, &mons, shiny);
+    sound::play_encounter(config, &mons, shiny);
+    if config.event_log {
+        let path = Path::new(&config.event_log_path);
+        if let Err(e) = append_encounter_event(path, &mons, shiny) {
+            warn!("Failed to write {}: {e}", config.event_log_path);
+        }
+    }
+    if let Some(location) = &location {
+        let stats = state.location_stats.entry(location.clone()).or_default();
+        for mon in &mons {
+            *stats.entry(mon.clone()).or_insert(0) += 1;
+        }
+    }
+    for mon in &mons {
+        *state.mon_stats.entry(mon.clone()).or_insert(0) += 1;
+    }
+    for (mon, level) in found {
+        if let Some(level) = level {
+            state.level_stats.entry(mon.clone()).or_default().record(*level);
+        }
+    }
 
-    if !state.in_encounter {
-        let cropped_wild = capture_bottom(state.debug, window)?;
-        let wilds = get_wild(engine, cropped_wild)?;
-        if wilds {
-            state.in_encounter = true;
-            println!("[DEBUG] Wild is detected, flag set to true.");
+    if let Some(milestone) = state.reached_milestone(&config.milestones) {
+        info!("Milestone reached: {milestone} encounters!");
+        state.last_milestone = milestone;
+        if config.milestone_notifications {
+            notify::send_milestone(milestone, config.language);
         }
     }
 
-    if state.in_encounter {
-        let cropped_image = capture_screen(state.debug, window)?;
-        let mons = get_mons(engine, cropped_image)?;
+    overlay::write_overlay(config, state);
+
+    state.unsaved_encounters += 1; // ✅ Increment inside EncounterState
+
+    if !state.dry_run && state.unsaved_encounters >= config.effective_save_interval() {
+        debug!("Saving progress...");
+        state.accrue_playtime();
+        save_state(state, false)?; // ✅ Save every `save_interval` encounters
+        state.unsaved_encounters = 0; // ✅ Reset counter after saving
+    }
+    Ok(counted)
+}
+
+// The counting state machine for one window, fed with already OCR'd lines. `wild_lines` is the
+// bottom strip, or None when it wasn't read because the window is already in an encounter and
+// no battle end phrases are configured.
+// Returns true when an encounter was counted. In `DetectOnly` mode it only records the lines.
+pub fn count_from_texts(
+    state: &mut EncounterState,
+    window_id: u32,
+    wild_lines: Option<&[String]>,
+    mon_lines: &[String],
+    config: &Config,
+) -> Result<bool, Box<dyn Error>> {
+    if let Some(lines) = wild_lines {
+        state.detected_wild_lines = lines.to_vec();
+    }
+    state.detected_mon_lines = mon_lines.to_vec();
+    if state.mode == PipelineMode::DetectOnly {
+        return Ok(false);
+    }
+    let mut win = state
+        .windows
+        .remove(&window_id)
+        .unwrap_or_else(|| WindowState::new(String::new()));
+    let result = update_counts(state, &mut win, window_id, wild_lines, mon_lines, config);
+    state.windows.insert(window_id, win);
+    result
+}
+
+fn update_counts(
+    state: &mut EncounterState,
+    win: &mut WindowState,
+    id: u32,
+    wild_lines: Option<&[String]>,
+    mon_lines: &[String],
+    config: &Config,
+) -> Result<bool, Box<dyn Error>> {
+    let mut encounter_detected = false;
+    let mut just_detected = false;
+    let cooling_down = win.rearm_cooldown > 0;
+    win.rearm_cooldown = win.rearm_cooldown.saturating_sub(1);
 
-        if !mons.is_empty() && state.is_not_counted {
-            println!("[DEBUG] Pokemon is detected.");
-            state.encounters += mons.len() as u32;
-            state.last_encounter = mons.clone();
-            state.is_not_counted = false;
-            for mon in mons {
-                *state.mon_stats.entry(mon.clone()).or_insert(0) += 1;
+    if !win.in_encounter {
+        let bottom_lines = wild_lines.unwrap_or_default();
+        let wilds = contains_any_phrase(bottom_lines, &config.wild_phrases);
+        let trainer = !wilds && contains_any_phrase(bottom_lines, &config.trainer_phrases);
+        if wilds || trainer {
+            win.in_encounter = true;
+            win.is_trainer = trainer;
+            win.frames_without_mons = 0;
+            just_detected = true;
+            if trainer {
+                debug!("Trainer battle is detected, flag set to true.");
+            } else {
+                debug!("Wild is detected, flag set to true.");
             }
+            win.is_shiny = has_shiny(bottom_lines);
+        }
+    }
 
-            state.unsaved_encounters += 1; // ✅ Increment inside EncounterState
-            encounter_detected = true; // ✅ Flag UI update needed
+    if win.in_encounter {
+        let mut found = parse_mon_levels(mon_lines, config);
+        if !config.effective_count_duplicates() {
+            let mut seen = HashSet::new();
+            found.retain(|(mon, _)| seen.insert(mon.clone()));
+        }
+        let mons: Vec<String> = found.iter().map(|(mon, _)| mon.clone()).collect();
+        let max_mons = config.effective_max_mons();
+        if max_mons > 0 && mons.len() > max_mons {
+            // No battle has this many, so the names came from UI noise. A later frame may
+            // still read the real ones.
+            warn!(
+                "Read {} names, more than max_mons_per_encounter ({}), skipping frame: {:?}",
+                mons.len(),
+                max_mons,
+                mons
+            );
+            return Ok(false);
+        }
+        // The closing message re-arms right away instead of waiting for the names to go. A
+        // battle whose names are on screen but not counted yet gets counted first.
+        let battle_end =
+            wild_lines.is_some_and(|lines| contains_any_phrase(lines, &config.battle_end_phrases));
+        if battle_end && !just_detected && (!win.is_not_counted || mons.is_empty()) {
+            win.end_battle();
+            debug!("Battle end text is detected, back to default.");
+            return Ok(false);
+        }
 
-            if state.unsaved_encounters >= 5 {
-                println!("[DEBUG] Saving progress...");
-                save_state(state, false)?; // ✅ Save every 5 encounters
-                state.unsaved_encounters = 0; // ✅ Reset counter after saving
+        let now = Utc::now();
+        let too_soon = cooling_down
+            || win.last_counted_at.is_some_and(|last| {
+                (now - last).num_milliseconds() < config.min_encounter_gap_ms as i64
+            });
+        let excluded_trainer = win.is_trainer && !config.count_trainers;
+        if !mons.is_empty() && win.is_not_counted && (too_soon || excluded_trainer) {
+            // Same battle read again right after counting it, or a trainer's team. Mark it
+            // counted so it is dropped.
+            if excluded_trainer {
+                debug!("Trainer Pokemon detected, not counting.");
+            } else {
+                debug!("Pokemon detected too soon after the last count, ignoring.");
             }
-            println!("[DEBUG] Counter completed, unsaved_encounters: {}.", state.unsaved_encounters);
-        } else {
-            if !state.is_not_counted {
-                state.in_encounter = false;
-                state.is_not_counted = true;
-                println!("[DEBUG] Encounter_process back to default.");
+            win.is_not_counted = false;
+        } else if !mons.is_empty() && win.is_not_counted {
+            debug!("Pokemon is detected.");
+            win.last_counted_at = Some(now);
+            win.rearm_cooldown = config.rearm_cooldown_frames;
+            win.encounters += config.effective_count_unit().increment(mons.len());
+            win.is_not_counted = false;
+            let (location, shiny) = (win.location.clone(), win.is_shiny);
+            record_encounter(state, &found, location, shiny, now, config)?;
+            state.last_window = Some(id);
+            if let Some(recent) = state.recent_encounters.back_mut() {
+                recent.window = Some(id);
+            }
+            encounter_detected = true; // ✅ Flag UI update needed
+            debug!("Counter completed, unsaved_encounters: {}.", state.unsaved_encounters);
+        } else if win.is_not_counted {
+            // Wild phrase seen but no names read yet, i.e. the text scrolled faster than they
+            // rendered. Keep retrying for `wild_grace_frames` polls, or forever when it is 0.
+            if !just_detected {
+                win.frames_without_mons += 1;
             }
+            if config.wild_grace_frames > 0 && win.frames_without_mons > config.wild_grace_frames {
+                win.in_encounter = false;
+                win.is_shiny = false;
+                win.is_trainer = false;
+                debug!("No Pokemon within wild_grace_frames, back to default.");
+            }
+        } else {
+            win.end_battle();
+            debug!("Encounter_process back to default.");
         }
     }
     Ok(encounter_detected)  // ✅ Return true if an encounter happened
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1198 @@
+use super::*;
+use crate::config::{Config, CountUnit};
+use std::path::PathBuf;
+
+// One entry of tests/fixtures/expected.json.
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    image: String,
+    wild: Option<bool>,
+    trainer: Option<bool>,
+    mons: Option<Vec<String>>,
+    // Capture color options to read this image with, overriding the defaults.
+    grayscale: Option<bool>,
+    threshold: Option<u8>,
+}
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn engine() -> OcrEngine {
+    let load = |name: &str| rten::Model::load(fs::read(root().join(name)).unwrap()).unwrap();
+    let config = Config::default();
+    OcrEngine::new(ocrs::OcrEngineParams {
+        detection_model: Some(load("text-detection.rten")),
+        recognition_model: Some(load("text-recognition.rten")),
+        decode_method: config.ocr_decode_method(),
+        allowed_chars: config.ocr_allowed_chars,
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+// The OCR test below is ignored by default, so this keeps the list it walks from going empty or
+// pointing at images that aren't there.
+#[test]
+fn fixtures_are_listed_and_load() {
+    let fixtures_dir = root().join("tests/fixtures");
+    let expected = fs::read_to_string(fixtures_dir.join("expected.json")).unwrap();
+    let fixtures: Vec<Fixture> = serde_json::from_str(&expected).unwrap();
+    assert!(!fixtures.is_empty());
+    for fixture in &fixtures {
+        let checks = fixture.wild.is_some() || fixture.trainer.is_some() || fixture.mons.is_some();
+        assert!(checks, "{} expects nothing", fixture.image);
+        image::open(fixtures_dir.join(&fixture.image)).unwrap();
+    }
+}
+
+#[test]
+#[ignore = "loads the OCR models, run with --ignored"]
+fn fixtures_match_expected() {
+    let fixtures_dir = root().join("tests/fixtures");
+    let expected = fs::read_to_string(fixtures_dir.join("expected.json")).unwrap();
+    let fixtures: Vec<Fixture> = serde_json::from_str(&expected).unwrap();
+    let engine = engine();
+
+    for fixture in fixtures {
+        let defaults = Config::default();
+        let config = Config {
+            grayscale: fixture.grayscale.unwrap_or(defaults.grayscale),
+            threshold: fixture.threshold.or(defaults.threshold),
+            ..defaults
+        };
+        let img = image::open(fixtures_dir.join(&fixture.image)).unwrap();
+        let img = prepare_crop(img, &config);
+        if fixture.wild.is_some() || fixture.trainer.is_some() {
+            let lines = confident_lines(&engine, img.clone(), config.min_confidence).unwrap();
+            if let Some(wild) = fixture.wild {
+                assert_eq!(
+                    contains_any_phrase(&lines, &config.wild_phrases),
+                    wild,
+                    "wild check for {}",
+                    fixture.image
+                );
+            }
+            if let Some(trainer) = fixture.trainer {
+                assert_eq!(
+                    contains_any_phrase(&lines, &config.trainer_phrases),
+                    trainer,
+                    "trainer check for {}",
+                    fixture.image
+                );
+            }
+        }
+        if let Some(mons) = fixture.mons {
+            let lines = confident_lines(&engine, img, config.min_confidence).unwrap();
+            let found = parse_mons(&lines, &config);
+            assert_eq!(found, mons, "mons read from {}", fixture.image);
+        }
+    }
+}
+
+// Gray text barely lighter than its plate: luminance about 61 for the plate, 93 for the text.
+const LOW_CONTRAST_FIXTURE: &str = "tests/fixtures/mons_low_contrast.png";
+const LOW_CONTRAST_THRESHOLD: u8 = 76;
+
+#[test]
+fn threshold_binarizes_low_contrast_fixture() {
+    let img = image::open(root().join(LOW_CONTRAST_FIXTURE)).unwrap();
+    let config = Config {
+        threshold: Some(LOW_CONTRAST_THRESHOLD),
+        ..Default::default()
+    };
+    let prepared = prepare_crop(img, &config);
+    assert!(prepared.pixels().all(|pixel| pixel.0 == [0; 3] || pixel.0 == [255; 3]));
+    // The plate goes black and the text stays, white.
+    let white = prepared.pixels().filter(|pixel| pixel.0 == [255; 3]).count();
+    assert!(white > 0 && white < prepared.pixels().count() / 4);
+}
+
+#[test]
+#[ignore = "loads the OCR models, run with --ignored"]
+fn threshold_reads_low_contrast_text() {
+    let engine = engine();
+    let img = image::open(root().join(LOW_CONTRAST_FIXTURE)).unwrap();
+    let plain = Config::default();
+    let binarized = Config {
+        threshold: Some(LOW_CONTRAST_THRESHOLD),
+        ..Default::default()
+    };
+    let read = |config: &Config| {
+        let lines = confident_lines(&engine, prepare_crop(img.clone(), config), 0.0).unwrap();
+        (parse_mons(&lines, config), lines)
+    };
+    let (_, plain_lines) = read(&plain);
+    let (mons, binarized_lines) = read(&binarized);
+    assert_eq!(
+        mons,
+        ["zubat"],
+        "binarized read {binarized_lines:?}, without the threshold {plain_lines:?}"
+    );
+}
+
+fn lines(texts: &[&str]) -> Vec<String> {
+    texts.iter().map(|text| text.to_string()).collect()
+}
+
+// State that never touches state.json, with no debounce unless a test sets one.
+fn test_state() -> (EncounterState, Config) {
+    let state = EncounterState {
+        dry_run: true,
+        ..Default::default()
+    };
+    let config = Config {
+        min_encounter_gap_ms: 0,
+        ..Default::default()
+    };
+    (state, config)
+}
+
+// Feeds one poll of window 1 to the state machine.
+fn poll(state: &mut EncounterState, config: &Config, wild: Option<&[&str]>, mons: &[&str]) -> bool {
+    let wild = wild.map(lines);
+    count_from_texts(state, 1, wild.as_deref(), &lines(mons), config).unwrap()
+}
+
+const WILD: &[&str] = &["a wild pidgey appeared!"];
+const PIDGEY: &[&str] = &["pidgey lv. 5"];
+
+#[test]
+fn parse_mons_snaps_names_before_level_markers() {
+    let texts = lines(&["pidqey lv. 5", "rattata nv. 3", "no level here"]);
+    let found = parse_mons(&texts, &Config::default());
+    assert_eq!(found, vec!["pidgey", "rattata"]);
+}
+
+#[test]
+fn parse_mons_reads_every_default_level_marker() {
+    let config = Config::default();
+    for marker in &config.level_markers {
+        let texts = lines(&[&format!("pidgey {marker} 5")]);
+        assert_eq!(parse_mons(&texts, &config), vec!["pidgey"], "marker {marker}");
+    }
+}
+
+#[test]
+fn parse_mons_reads_multi_word_names() {
+    let config = Config::default();
+    let texts = lines(&["mr. mime lv. 20", "wild mime jr. lv. 5", "pid gey lv. 3"]);
+    assert_eq!(parse_mons(&texts, &config), vec!["mr. mime", "mime jr.", "pidgey"]);
+}
+
+#[test]
+fn parse_mons_splits_names_on_one_line() {
+    let config = Config::default();
+    let texts = lines(&["pidgey lv. 5 rattata lv. 3 mr. mime lv. 9"]);
+    assert_eq!(parse_mons(&texts, &config), vec!["pidgey", "rattata", "mr. mime"]);
+}
+
+#[test]
+fn parse_mons_respects_max_name_tokens() {
+    let config = Config {
+        max_name_tokens: 1,
+        ..Default::default()
+    };
+    // Only "mime" is tried, which is too far from "mr. mime" to snap to it.
+    assert!(parse_mons(&lines(&["mr. mime lv. 20"]), &config).is_empty());
+}
+
+#[test]
+fn nothing_counted_without_wild_text() {
+    let (mut state, config) = test_state();
+    assert!(!poll(&mut state, &config, Some(&[]), PIDGEY));
+    assert!(!poll(&mut state, &config, Some(&["you got 3 potions"]), PIDGEY));
+    assert_eq!(state.encounters, 0);
+    assert!(!state.windows[&1].in_encounter);
+}
+
+#[test]
+fn counts_once_per_battle() {
+    let (mut state, config) = test_state();
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    // Still in battle: the bottom strip isn't read and the same mons stay on screen.
+    assert!(!poll(&mut state, &config, None, PIDGEY));
+    assert!(!poll(&mut state, &config, None, PIDGEY));
+    assert_eq!(state.encounters, 1);
+    assert_eq!(state.last_encounter, vec!["pidgey"]);
+    assert_eq!(state.mon_stats["pidgey"], 1);
+    assert_eq!(state.windows[&1].encounters, 1);
+    assert_eq!(state.last_window, Some(1));
+    assert_eq!(state.recent_encounters.len(), 1);
+}
+
+#[test]
+fn wild_text_waits_for_names() {
+    let (mut state, config) = test_state();
+    assert!(!poll(&mut state, &config, Some(WILD), &[]));
+    assert!(state.windows[&1].in_encounter);
+    assert!(poll(&mut state, &config, None, PIDGEY));
+    assert_eq!(state.encounters, 1);
+}
+
+#[test]
+fn resets_after_battle_ends() {
+    let (mut state, config) = test_state();
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert!(!poll(&mut state, &config, None, &[]));
+    let win = &state.windows[&1];
+    assert!(!win.in_encounter);
+    assert!(win.is_not_counted);
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert_eq!(state.encounters, 2);
+    assert_eq!(state.current_chain, 2);
+}
+
+#[test]
+fn since_target_resets_on_target() {
+    let (mut state, mut config) = test_state();
+    config.target_species = Some("Rattata".to_string());
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    poll(&mut state, &config, None, &[]);
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    poll(&mut state, &config, None, &[]);
+    assert_eq!(state.encounters_since_target, 2);
+    assert!(poll(&mut state, &config, Some(WILD), &["rattata lv. 3"]));
+    assert_eq!(state.encounters_since_target, 0);
+}
+
+#[test]
+fn detect_only_records_lines_without_counting() {
+    let (mut state, config) = test_state();
+    state.mode = PipelineMode::DetectOnly;
+    assert!(!poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert_eq!(state.encounters, 0);
+    assert!(state.mon_stats.is_empty());
+    assert!(!state.windows.get(&1).is_some_and(|win| win.in_encounter));
+    assert_eq!(state.detected_wild_lines, lines(WILD));
+    assert_eq!(state.detected_mon_lines, lines(PIDGEY));
+
+    state.mode = PipelineMode::Count;
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert_eq!(state.encounters, 1);
+}
+
+#[test]
+fn rearm_cooldown_drops_counts_right_after_one() {
+    let (mut state, mut config) = test_state();
+    config.rearm_cooldown_frames = 2;
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert!(!poll(&mut state, &config, None, &[]));
+    // The victory screen reads like a new battle, but the cooldown isn't over.
+    assert!(!poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert!(!poll(&mut state, &config, None, &[]));
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert_eq!(state.encounters, 2);
+}
+
+#[test]
+fn too_many_names_is_noise() {
+    let (mut state, mut config) = test_state();
+    config.max_mons_per_encounter = 2;
+    let noise = &[
+        "pidgey lv. 5",
+        "rattata lv. 3",
+        "abra lv. 7",
+        "zubat lv. 4",
+        "oddish lv. 9",
+        "paras lv. 8",
+        "venonat lv. 6",
+        "psyduck lv. 2",
+    ];
+    assert!(!poll(&mut state, &config, Some(WILD), noise));
+    assert_eq!(state.encounters, 0);
+    // Still in the battle, so the next clean read counts it.
+    assert!(state.windows[&1].in_encounter);
+    assert!(poll(&mut state, &config, None, PIDGEY));
+    assert_eq!(state.encounters, 1);
+}
+
+#[test]
+fn battles_unit_counts_a_horde_once() {
+    let (mut state, mut config) = test_state();
+    config.count_unit = CountUnit::Battles;
+    config.max_mons_per_encounter = 3;
+    config.target_species = Some("abra".to_string());
+    let horde = &["pidgey lv. 5", "pidgey lv. 4", "rattata lv. 3"];
+    assert!(poll(&mut state, &config, Some(WILD), horde));
+    assert_eq!(state.encounters, 1);
+    assert_eq!(state.mons_seen(), 3);
+    assert_eq!(state.mon_stats["pidgey"], 2);
+    assert_eq!(state.encounters_since_target, 1);
+
+    assert!(undo_last_encounter(&mut state, CountUnit::Battles));
+    assert_eq!(state.encounters, 0);
+    assert_eq!(state.mons_seen(), 0);
+}
+
+#[test]
+fn battle_end_text_rearms_while_names_still_show() {
+    let (mut state, mut config) = test_state();
+    config.battle_end_phrases = lines(&["fled!"]);
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    // Names still on screen, but the message says the battle is over.
+    let fled: &[&str] = &["pidgey fled!"];
+    assert!(!poll(&mut state, &config, Some(fled), PIDGEY));
+    let win = &state.windows[&1];
+    assert!(!win.in_encounter);
+    assert!(win.is_not_counted);
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert_eq!(state.encounters, 2);
+}
+
+#[test]
+fn battle_end_text_waits_for_the_count() {
+    let (mut state, mut config) = test_state();
+    config.battle_end_phrases = lines(&["pidgey fainted"]);
+    assert!(!poll(&mut state, &config, Some(WILD), &[]));
+    // Names and the closing message in one frame: count first, close on the next poll.
+    let fainted: &[&str] = &["wild pidgey fainted!"];
+    assert!(poll(&mut state, &config, Some(fainted), PIDGEY));
+    assert!(!poll(&mut state, &config, Some(fainted), PIDGEY));
+    assert!(!state.windows[&1].in_encounter);
+    assert_eq!(state.encounters, 1);
+}
+
+#[test]
+fn battle_end_phrases_follow_config() {
+    let phrases = lines(&["Got away safely"]);
+    assert!(contains_any_phrase(&lines(&["you got away safely!"]), &phrases));
+    assert!(!contains_any_phrase(&lines(&["pidgey used tackle!"]), &phrases));
+    assert!(!contains_any_phrase(&lines(&["anything"]), &lines(&[" "])));
+}
+
+#[test]
+fn duplicates_follow_config() {
+    let (mut state, mut config) = test_state();
+    config.count_duplicates = false;
+    config.max_mons_per_encounter = 3;
+    let horde = &["pidgey lv. 5", "pidgey lv. 4", "rattata lv. 3"];
+    assert!(poll(&mut state, &config, Some(WILD), horde));
+    assert_eq!(state.encounters, 2);
+
+    config.count_duplicates = true;
+    poll(&mut state, &config, None, &[]);
+    assert!(poll(&mut state, &config, Some(WILD), horde));
+    assert_eq!(state.encounters, 5);
+    assert_eq!(state.mon_stats["pidgey"], 3);
+}
+
+#[test]
+fn duplicate_names_in_one_battle_count_once() {
+    let (mut state, mut config) = test_state();
+    config.count_duplicates = false;
+    let twins = &["pidgey lv. 5", "pidgey lv. 4"];
+    assert!(poll(&mut state, &config, Some(WILD), twins));
+    assert_eq!(state.encounters, 1);
+    assert_eq!(state.mon_stats["pidgey"], 1);
+    assert_eq!(state.last_encounter, vec!["pidgey"]);
+}
+
+#[test]
+fn trainer_battles_follow_config() {
+    let (mut state, mut config) = test_state();
+    let trainer = &["youngster joey would like to battle!"];
+    assert!(!poll(&mut state, &config, Some(trainer), PIDGEY));
+    assert!(!poll(&mut state, &config, None, PIDGEY));
+    assert_eq!(state.encounters, 0);
+    poll(&mut state, &config, None, &[]);
+    assert!(!state.windows[&1].is_trainer);
+
+    config.count_trainers = true;
+    assert!(poll(&mut state, &config, Some(trainer), PIDGEY));
+    assert_eq!(state.encounters, 1);
+}
+
+#[test]
+fn min_gap_drops_rereads() {
+    let (mut state, mut config) = test_state();
+    config.min_encounter_gap_ms = 60_000;
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    // The names flickered out for a poll, then the same battle is read again.
+    poll(&mut state, &config, None, &[]);
+    assert!(!poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert_eq!(state.encounters, 1);
+}
+
+#[test]
+fn shiny_counted_with_encounter() {
+    let (mut state, config) = test_state();
+    let shiny = &["a wild shiny pidgey appeared!"];
+    assert!(poll(&mut state, &config, Some(shiny), PIDGEY));
+    assert_eq!(state.shiny_encounters, 1);
+    assert_eq!(state.shiny_mons, vec!["pidgey"]);
+    poll(&mut state, &config, None, &[]);
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert_eq!(state.shiny_encounters, 1);
+}
+
+#[test]
+fn windows_are_counted_separately() {
+    let (mut state, config) = test_state();
+    let wild = lines(WILD);
+    assert!(count_from_texts(&mut state, 1, Some(&wild), &lines(PIDGEY), &config).unwrap());
+    assert!(count_from_texts(&mut state, 2, Some(&wild), &lines(PIDGEY), &config).unwrap());
+    assert_eq!(state.encounters, 2);
+    assert_eq!(state.windows[&1].encounters, 1);
+    assert_eq!(state.windows[&2].encounters, 1);
+}
+
+#[test]
+fn undo_with_nothing_counted_is_a_no_op() {
+    let (mut state, _) = test_state();
+    state.start_session();
+    assert!(!undo_last_encounter(&mut state, CountUnit::Mons));
+    assert_eq!(state.encounters, 0);
+    assert!(state.mon_stats.is_empty());
+    assert_eq!(state.sessions[0].encounters, 0);
+}
+
+#[test]
+fn repeated_undos_take_back_one_encounter_at_a_time() {
+    let (mut state, config) = test_state();
+    state.start_session();
+    let rattata: &[&str] = &["rattata lv. 3"];
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    poll(&mut state, &config, None, &[]);
+    assert!(poll(&mut state, &config, Some(WILD), rattata));
+    assert_eq!(state.encounters, 2);
+
+    assert!(undo_last_encounter(&mut state, CountUnit::Mons));
+    assert_eq!(state.encounters, 1);
+    assert!(!state.mon_stats.contains_key("rattata"));
+    assert_eq!(state.last_encounter, vec!["pidgey"]);
+    assert_eq!(state.windows[&1].encounters, 1);
+
+    assert!(undo_last_encounter(&mut state, CountUnit::Mons));
+    assert_eq!(state.encounters, 0);
+    assert!(state.mon_stats.is_empty());
+    assert_eq!(state.windows[&1].encounters, 0);
+    assert_eq!(state.sessions[0].encounters, 0);
+
+    // Nothing left: more undos change nothing, and no count wraps around below zero.
+    assert!(!undo_last_encounter(&mut state, CountUnit::Mons));
+    assert_eq!(state.encounters, 0);
+    assert_eq!(state.windows[&1].encounters, 0);
+}
+
+#[test]
+fn undo_stops_at_the_session_start() {
+    let (mut state, config) = test_state();
+    state.start_session();
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    poll(&mut state, &config, None, &[]);
+    state.reset_session();
+    assert!(poll(&mut state, &config, Some(WILD), &["rattata lv. 3"]));
+
+    assert!(undo_last_encounter(&mut state, CountUnit::Mons));
+    assert!(state.last_encounter.is_empty());
+    assert!(!undo_last_encounter(&mut state, CountUnit::Mons));
+    assert_eq!(state.mon_stats["pidgey"], 1);
+}
+
+#[test]
+fn encounters_tagged_with_window_location() {
+    let (mut state, config) = test_state();
+    let mut win = WindowState::new("PokeMMO".to_string());
+    win.location = Some("route 1".to_string());
+    state.windows.insert(1, win);
+    assert!(poll(&mut state, &config, Some(WILD), PIDGEY));
+    assert_eq!(state.location_stats["route 1"]["pidgey"], 1);
+    assert_eq!(state.recent_encounters[0].location.as_deref(), Some("route 1"));
+
+    assert!(undo_last_encounter(&mut state, CountUnit::Mons));
+    assert!(state.location_stats["route 1"].is_empty());
+}
+
+#[test]
+fn wild_phrases_match_each_language() {
+    let phrases = Config::default().wild_phrases;
+    for message in [
+        "a wild pidgey appeared!",
+        "ein wildes taubsi erscheint!",
+        "un roucool sauvage apparaît !",
+        "¡un pidgey salvaje apareció!",
+        "appare un pidgey selvatico!",
+        "um pidgey selvagem apareceu!",
+    ] {
+        assert!(contains_any_phrase(&lines(&[message]), &phrases), "{message}");
+    }
+    assert!(!contains_any_phrase(&lines(&["pidgey used tackle!"]), &phrases));
+}
+
+#[test]
+fn wild_phrases_are_configurable() {
+    let phrases = vec!["Wild Encounter".to_string()];
+    assert!(contains_any_phrase(&lines(&["wild encounter: pidgey"]), &phrases));
+    assert!(!contains_any_phrase(&lines(WILD), &phrases));
+}
+
+#[test]
+fn names_retried_within_grace_frames() {
+    let (mut state, mut config) = test_state();
+    config.wild_grace_frames = 2;
+    assert!(!poll(&mut state, &config, Some(WILD), &[]));
+    assert!(!poll(&mut state, &config, None, &[]));
+    assert!(poll(&mut state, &config, None, PIDGEY));
+    assert_eq!(state.encounters, 1);
+}
+
+#[test]
+fn gives_up_after_grace_frames() {
+    let (mut state, mut config) = test_state();
+    config.wild_grace_frames = 2;
+    poll(&mut state, &config, Some(WILD), &[]);
+    poll(&mut state, &config, None, &[]);
+    poll(&mut state, &config, None, &[]);
+    assert!(state.windows[&1].in_encounter);
+    poll(&mut state, &config, None, &[]);
+    assert!(!state.windows[&1].in_encounter);
+    // Back to reading the bottom strip, so names alone don't count.
+    assert!(!poll(&mut state, &config, Some(&[]), PIDGEY));
+    assert_eq!(state.encounters, 0);
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("lineuz_{}_{}", std::process::id(), name))
+}
+
+#[test]
+fn exported_state_imports_back() {
+    let (mut state, config) = test_state();
+    poll(&mut state, &config, Some(WILD), PIDGEY);
+    let path = temp_file("export.json");
+    export_state(&state, &path).unwrap();
+    let imported = import_state(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(imported.encounters, 1);
+    assert_eq!(imported.mon_stats, state.mon_stats);
+}
+
+#[test]
+fn import_rejects_malformed_files() {
+    let path = temp_file("malformed.json");
+    for contents in [
+        "not json",
+        r#"{"version": 1, "state": {"encounters": "many"}, "crashed": false}"#,
+        r#"{"version": 99, "state": {}, "crashed": false}"#,
+    ] {
+        fs::write(&path, contents).unwrap();
+        assert!(import_state(&path).is_err(), "{contents}");
+    }
+
+    // More Pokemon than even a horde per encounter.
+    let state = EncounterState {
+        encounters: 1,
+        mon_stats: HashMap::from([("pidgey".to_string(), 6)]),
+        ..Default::default()
+    };
+    export_state(&state, &path).unwrap();
+    assert!(import_state(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn flush_state_ends_the_session() {
+    let (mut state, _) = test_state();
+    state.start_session();
+    let state = Arc::new(Mutex::new(state));
+    flush_state(&state, Duration::from_secs(1), false).unwrap();
+    assert!(state.lock().unwrap().sessions[0].ended_at.is_some());
+}
+
+#[test]
+fn flush_state_gives_up_on_a_held_lock() {
+    let (state, _) = test_state();
+    let state = Arc::new(Mutex::new(state));
+    let guard = state.lock().unwrap();
+    assert!(flush_state(&state, Duration::from_millis(50), false).is_err());
+    drop(guard);
+}
+
+#[test]
+fn flush_state_saves_through_a_poisoned_lock() {
+    let (state, _) = test_state();
+    let state = Arc::new(Mutex::new(state));
+    let poisoner = Arc::clone(&state);
+    let _ = thread::spawn(move || {
+        let _guard = poisoner.lock().unwrap();
+        panic!("poison the lock");
+    })
+    .join();
+    assert!(state.is_poisoned());
+    flush_state(&state, Duration::from_secs(1), true).unwrap();
+}
+
+#[test]
+fn milestones_fire_once() {
+    let (mut state, mut config) = test_state();
+    config.milestones = vec![1, 3];
+    config.milestone_notifications = false;
+    poll(&mut state, &config, Some(WILD), PIDGEY);
+    assert_eq!(state.last_milestone, 1);
+
+    // Undoing and counting again must not announce 1 a second time.
+    undo_last_encounter(&mut state, CountUnit::Mons);
+    poll(&mut state, &config, None, &[]);
+    poll(&mut state, &config, Some(WILD), PIDGEY);
+    assert_eq!(state.last_milestone, 1);
+
+    let horde = &["pidgey lv. 5", "rattata lv. 3", "abra lv. 4"];
+    config.max_mons_per_encounter = 3;
+    poll(&mut state, &config, None, &[]);
+    poll(&mut state, &config, Some(WILD), horde);
+    assert_eq!(state.encounters, 4);
+    assert_eq!(state.last_milestone, 3);
+}
+
+// Fresh empty folder for tests that write state files.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = temp_file(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn load_state_migrates_bare_state() {
+    let dir = temp_dir("migrate");
+    let path = dir.join("state.json");
+    let old = EncounterState {
+        encounters: 7,
+        mon_stats: HashMap::from([("pidgey".to_string(), 7)]),
+        ..Default::default()
+    };
+    fs::write(&path, serde_json::to_string(&old).unwrap()).unwrap();
+
+    let loaded = load_state_from(&path).unwrap();
+    assert_eq!(loaded.encounters, 7);
+    assert_eq!(loaded.mon_stats, old.mon_stats);
+
+    let rewritten: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(rewritten["version"], STATE_VERSION);
+    assert_eq!(rewritten["state"]["encounters"], 7);
+    // The old-format file is what the backup keeps.
+    let backup = fs::read_to_string(dir.join("state.json.bak")).unwrap();
+    assert!(serde_json::from_str::<serde_json::Value>(&backup).unwrap().get("version").is_none());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn load_state_errors_on_corrupt_file_without_backup() {
+    let dir = temp_dir("corrupt");
+    let path = dir.join("state.json");
+    fs::write(&path, "{ not json").unwrap();
+    assert!(load_state_from(&path).is_err());
+    // The corrupt file is left alone for the user to look at.
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{ not json");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn load_state_restores_corrupt_file_from_backup() {
+    let dir = temp_dir("restore");
+    let path = dir.join("state.json");
+    let state = EncounterState {
+        encounters: 3,
+        ..Default::default()
+    };
+    save_state_to(&state, false, &path).unwrap();
+    fs::copy(&path, dir.join("state.json.bak")).unwrap();
+    fs::write(&path, "{ not json").unwrap();
+
+    assert_eq!(load_state_from(&path).unwrap().encounters, 3);
+    assert!(read_saved_state(&path).is_ok());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_state_file_is_an_error() {
+    let dir = temp_dir("missing");
+    assert!(load_state_from(&dir.join("state.json")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn poisoned_lock_recovers_its_data() {
+    let mutex = Arc::new(Mutex::new(5));
+    let held = Arc::clone(&mutex);
+    let _ = thread::spawn(move || {
+        let _guard = held.lock().unwrap();
+        panic!("poisons the lock");
+    })
+    .join();
+    assert!(mutex.is_poisoned());
+    assert_eq!(*lock_or_recover(&mutex), 5);
+    assert!(!mutex.is_poisoned());
+}
+
+#[test]
+fn consolidate_stats_merges_misreads() {
+    let mut state = EncounterState {
+        mon_stats: HashMap::from([
+            ("pidgey".to_string(), 10),
+            ("pidqey".to_string(), 2),
+            ("pidgey.".to_string(), 1),
+            ("Rattata".to_string(), 4),
+            ("zzzz".to_string(), 1),
+        ]),
+        last_encounter: vec!["pidqey".to_string()],
+        ..Default::default()
+    };
+    let cleanup = consolidate_stats(&mut state, 2);
+    assert_eq!(cleanup.species_before, 5);
+    assert_eq!(cleanup.species_after, 3);
+    assert_eq!(
+        cleanup.renamed,
+        vec![
+            ("Rattata".to_string(), "rattata".to_string()),
+            ("pidgey.".to_string(), "pidgey".to_string()),
+            ("pidqey".to_string(), "pidgey".to_string()),
+        ]
+    );
+    assert_eq!(state.mon_stats["pidgey"], 13);
+    assert_eq!(state.mon_stats["rattata"], 4);
+    // Nothing to snap to, so it stays for the user to judge.
+    assert_eq!(state.mon_stats["zzzz"], 1);
+    assert_eq!(state.last_encounter, vec!["pidgey"]);
+
+    assert!(undo_last_encounter(&mut state, CountUnit::Mons));
+    assert_eq!(state.mon_stats["pidgey"], 12);
+    assert!(consolidate_stats(&mut state, 2).renamed.is_empty());
+}
+
+#[test]
+fn smoothed_rate_averages_intervals_within_the_session() {
+    let mut state = EncounterState::default();
+    state.start_session();
+    let start = state.sessions.last().unwrap().started_at;
+    state.last_encounter_time = Some(start - chrono::Duration::hours(1));
+    // The previous count is from before this session, so it says nothing about the pace.
+    state.update_smoothed_interval(start, 1, 0.5);
+    assert_eq!(state.smoothed_rate(), None);
+
+    state.last_encounter_time = Some(start);
+    state.update_smoothed_interval(start + chrono::Duration::seconds(10), 1, 0.5);
+    assert_eq!(state.smoothed_rate(), Some(360.0));
+    state.last_encounter_time = Some(start + chrono::Duration::seconds(10));
+    // A horde of 2 in 40s is 20s per encounter: 0.5 * 20 + 0.5 * 10 = 15s.
+    state.update_smoothed_interval(start + chrono::Duration::seconds(50), 2, 0.5);
+    assert_eq!(state.smoothed_rate(), Some(240.0));
+
+    state.reset_session();
+    assert_eq!(state.smoothed_rate(), None);
+}
+
+#[test]
+fn join_reads_gives_the_same_results_either_way() {
+    let read = |text: &str| vec![text.to_string()];
+    let parallel = join_reads(true, || read("a wild pidgey"), || read("pidgey lv. 3"));
+    let sequential = join_reads(false, || read("a wild pidgey"), || read("pidgey lv. 3"));
+    assert_eq!(parallel, sequential);
+    assert_eq!(parallel.0, vec!["a wild pidgey".to_string()]);
+}
+
+#[test]
+#[ignore = "benchmark, loads the OCR models, run with --ignored --nocapture"]
+fn bench_join_reads_parallel_against_sequential() {
+    const ROUNDS: u32 = 5;
+    let engine = engine();
+    let fixtures_dir = root().join("tests/fixtures");
+    let strip = image::open(fixtures_dir.join("wild_pidgey.png")).unwrap().to_rgb8();
+    let names = image::open(fixtures_dir.join("mons_horde.png")).unwrap().to_rgb8();
+    let read = |crop: &RgbImage| confident_lines(&engine, crop.clone(), 0.0).unwrap();
+    let time = |parallel: bool| {
+        let start = Instant::now();
+        let mut reads = None;
+        for _ in 0..ROUNDS {
+            reads = Some(join_reads(parallel, || read(&strip), || read(&names)));
+        }
+        (start.elapsed() / ROUNDS, reads.unwrap())
+    };
+    // Warm up the pool and the models first.
+    time(true);
+    let (parallel_time, parallel_reads) = time(true);
+    let (sequential_time, sequential_reads) = time(false);
+    println!("both crops in parallel {parallel_time:?}, one after the other {sequential_time:?}");
+    assert_eq!(parallel_reads, sequential_reads);
+}
+
+fn region(start_x: f32, end_x: f32, start_y: f32, end_y: f32) -> CaptureRegion {
+    CaptureRegion {
+        start_x,
+        end_x,
+        start_y,
+        end_y,
+        debug_filename: "debug.png".to_string(),
+    }
+}
+
+#[test]
+fn tiny_window_is_an_error_not_a_panic() {
+    let regions = CaptureRegions::default();
+    for region in [&regions.wild, &regions.mons, &regions.location] {
+        assert!(crop_bounds(10, 10, region).is_err());
+    }
+    // The same through the capture path, on a synthetic 10x10 window.
+    let frame = RgbaImage::new(10, 10);
+    assert!(capture_crop(false, &frame, &regions.mons, &Config::default()).is_err());
+}
+
+#[test]
+fn crop_bounds_clamps_to_the_window() {
+    // Past every edge: clamped to the whole width and the lower part of the height.
+    assert_eq!(crop_bounds(100, 100, &region(-0.5, 1.5, 0.25, 2.0)).unwrap(), (0, 25, 100, 75));
+    assert_eq!(crop_bounds(200, 100, &region(0.5, 1.0, 0.0, 0.5)).unwrap(), (100, 0, 100, 50));
+    // Ends before it starts: an empty crop, not an underflowing width.
+    assert!(crop_bounds(100, 100, &region(0.75, 0.25, 0.0, 1.0)).is_err());
+}
+
+#[test]
+fn sanitize_regions_clamps_and_swaps_ratios() {
+    let regions = CaptureRegions {
+        wild: region(0.7, 0.06, 0.6, 0.78),
+        mons: region(-0.5, 1.5, 0.06, 0.3),
+        location: region(0.0, 0.3, 0.0, 0.06),
+    };
+    let fixed = sanitize_regions(regions);
+    assert_eq!(fixed.wild, region(0.06, 0.7, 0.6, 0.78));
+    assert_eq!(fixed.mons, region(0.0, 1.0, 0.06, 0.3));
+    assert_eq!(fixed.location, region(0.0, 0.3, 0.0, 0.06));
+}
+
+#[test]
+fn sanitize_regions_falls_back_to_defaults_for_unusable_regions() {
+    let defaults = CaptureRegions::default();
+    let regions = CaptureRegions {
+        // Empty once clamped: both ends land on 1.0.
+        wild: region(1.2, 3.0, 0.6, 0.78),
+        mons: region(0.06, 0.94, f32::NAN, 0.3),
+        location: region(0.2, 0.2, 0.0, 0.06),
+    };
+    let fixed = sanitize_regions(regions);
+    assert_eq!(fixed, defaults);
+    for region in [&fixed.wild, &fixed.mons, &fixed.location] {
+        assert!(region.validate("Test", Lang::En).is_empty());
+    }
+}
+
+#[test]
+fn parse_mon_levels_reads_spaced_and_joined_levels() {
+    let texts = lines(&["pidgey lv. 5", "rattata lv.23", "mr. mime lvl.9,", "oddish lv. ?"]);
+    let found = parse_mon_levels(&texts, &Config::default());
+    assert_eq!(
+        found,
+        vec![
+            ("pidgey".to_string(), Some(5)),
+            ("rattata".to_string(), Some(23)),
+            ("mr. mime".to_string(), Some(9)),
+            ("oddish".to_string(), None),
+        ]
+    );
+}
+
+#[test]
+fn counting_records_level_stats() {
+    let (mut state, config) = test_state();
+    assert!(poll(&mut state, &config, Some(WILD), &["pidgey lv. 5"]));
+    poll(&mut state, &config, None, &[]);
+    assert!(poll(&mut state, &config, Some(WILD), &["pidgey lv.9"]));
+
+    let levels = state.level_stats["pidgey"];
+    assert_eq!((levels.count, levels.min, levels.max), (2, 5, 9));
+    assert_eq!(levels.average(), Some(7.0));
+}
+
+fn state_with(encounters: u32, stats: &[(&str, u32)], at: DateTime<Utc>) -> EncounterState {
+    EncounterState {
+        encounters,
+        mon_stats: stats.iter().map(|(mon, n)| (mon.to_string(), *n)).collect(),
+        last_encounter: vec![stats[0].0.to_string()],
+        last_encounter_time: Some(at),
+        sessions: vec![Session {
+            started_at: at - chrono::Duration::hours(1),
+            ended_at: Some(at),
+            encounters,
+            mons: HashMap::new(),
+        }],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn merge_states_adds_overlapping_species() {
+    let now = Utc::now();
+    let a = state_with(5, &[("pidgey", 3), ("rattata", 2)], now - chrono::Duration::days(1));
+    let mut b = state_with(4, &[("pidgey", 4)], now);
+    b.chain_species = Some("pidgey".to_string());
+    b.current_chain = 4;
+
+    let merged = merge_states(a.clone(), b.clone());
+    assert_eq!(merged.encounters, 9);
+    assert_eq!(merged.mon_stats["pidgey"], 7);
+    assert_eq!(merged.mon_stats["rattata"], 2);
+    // The chain belongs to the state that counted last, whichever argument it was.
+    assert_eq!(merged.current_chain, 4);
+    assert_eq!(merged.sessions.len(), 2);
+    assert!(merged.sessions[0].started_at < merged.sessions[1].started_at);
+    assert_eq!(merge_states(b, a), merged);
+}
+
+#[test]
+fn merge_states_keeps_disjoint_species() {
+    let now = Utc::now();
+    let a = state_with(2, &[("oddish", 2)], now);
+    let b = state_with(1, &[("zubat", 1)], now - chrono::Duration::hours(3));
+    let merged = merge_states(a, b);
+    assert_eq!(merged.encounters, 3);
+    assert_eq!(merged.mon_stats.len(), 2);
+    assert_eq!(merged.mons_seen(), 3);
+    assert_eq!(merged.last_encounter, vec!["oddish"]);
+}
+
+#[test]
+fn frame_timings_average_each_window() {
+    let mut timings = FrameTimings::default();
+    let frame = |ms: u64| StageTimes {
+        capture: Duration::from_millis(ms),
+        recognize_text: Duration::from_millis(ms * 2),
+        ..Default::default()
+    };
+    for i in 0..FRAME_TIMING_WINDOW - 1 {
+        timings.record(&frame(if i % 2 == 0 { 2 } else { 4 }));
+    }
+    assert_eq!(timings.average, None);
+    timings.record(&frame(4));
+    let average = timings.average.unwrap();
+    assert_eq!(average.capture, Duration::from_millis(3));
+    assert_eq!(average.recognize_text, Duration::from_millis(6));
+    assert_eq!(average.detect_words, Duration::ZERO);
+}
+
+#[test]
+fn strip_fingerprint_ignores_small_changes_within_threshold() {
+    let overworld = RgbImage::from_fn(320, 80, |x, y| image::Rgb([(x + y) as u8 % 64 + 80; 3]));
+    // Animated tiles: every pixel a little brighter.
+    let mut shimmer = overworld.clone();
+    for pixel in shimmer.pixels_mut() {
+        pixel.0 = pixel.0.map(|c| c + 6);
+    }
+    // A white dialog box over the left half of the strip.
+    let mut dialog = overworld.clone();
+    for (x, _, pixel) in dialog.enumerate_pixels_mut() {
+        if x < 160 {
+            pixel.0 = [255; 3];
+        }
+    }
+    let before = StripFingerprint::new(&overworld);
+    assert!(StripFingerprint::new(&overworld).unchanged_from(&before, 0));
+    assert!(!StripFingerprint::new(&shimmer).unchanged_from(&before, 0));
+    assert!(StripFingerprint::new(&shimmer).unchanged_from(&before, 15));
+    assert!(!StripFingerprint::new(&dialog).unchanged_from(&before, 15));
+}
+
+// Walking around: the overworld strip with a little noise on every frame, then the wild message.
+fn overworld_strip_sequence(frames: usize) -> Vec<RgbImage> {
+    let fixtures_dir = root().join("tests/fixtures");
+    let overworld = image::open(fixtures_dir.join("strip_overworld.png")).unwrap().to_rgb8();
+    let wild = image::open(fixtures_dir.join("wild_pidgey.png")).unwrap().to_rgb8();
+    let mut rng = fastrand::Rng::with_seed(7);
+    let mut sequence: Vec<RgbImage> = (0..frames)
+        .map(|_| {
+            let mut frame = overworld.clone();
+            for pixel in frame.pixels_mut() {
+                pixel.0 = pixel.0.map(|c| c.saturating_add(rng.u8(..4)));
+            }
+            frame
+        })
+        .collect();
+    sequence.push(wild);
+    sequence
+}
+
+#[test]
+#[ignore = "benchmark, loads the OCR models, run with --ignored --nocapture"]
+fn bench_fingerprint_skip_against_ocr_every_frame() {
+    let engine = engine();
+    let config = Config {
+        motion_threshold: 15,
+        ..Default::default()
+    };
+    let sequence = overworld_strip_sequence(30);
+    let is_wild = |strip: &RgbImage| {
+        let lines = confident_lines(&engine, strip.clone(), config.min_confidence).unwrap();
+        contains_any_phrase(&lines, &config.wild_phrases)
+    };
+
+    let start = Instant::now();
+    let every_frame: Vec<bool> = sequence.iter().map(is_wild).collect();
+    let every_frame_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut last_bottom: Option<StripFingerprint> = None;
+    let mut reads = 0;
+    let mut with_skip = Vec::new();
+    for strip in &sequence {
+        let bottom = StripFingerprint::new(strip);
+        if last_bottom
+            .as_ref()
+            .is_some_and(|last| bottom.unchanged_from(last, config.motion_threshold))
+        {
+            with_skip.push(false);
+            continue;
+        }
+        reads += 1;
+        let wild = is_wild(strip);
+        if !wild {
+            last_bottom = Some(bottom);
+        }
+        with_skip.push(wild);
+    }
+    let skip_time = start.elapsed();
+
+    println!(
+        "{} frames: OCR every frame {:?}, fingerprint skip {:?} ({} OCR reads)",
+        sequence.len(),
+        every_frame_time,
+        skip_time,
+        reads
+    );
+    // Both see the wild message on the last frame and nothing before it.
+    assert_eq!(every_frame, with_skip);
+    assert_eq!(every_frame.iter().filter(|wild| **wild).count(), 1);
+    assert!(reads < sequence.len());
+    assert!(skip_time < every_frame_time);
+}
+
+// Blank frames, counting how often the poll asked for one.
+struct CountingSource {
+    captures: std::sync::atomic::AtomicUsize,
+}
+
+impl FrameSource for CountingSource {
+    fn id(&self) -> u32 {
+        1
+    }
+
+    fn name(&self) -> String {
+        "counting".to_string()
+    }
+
+    fn capture(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        self.captures.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Ok(RgbaImage::new(1280, 720))
+    }
+}
+
+#[test]
+#[ignore = "loads the OCR models, run with --ignored"]
+fn each_poll_captures_one_frame() {
+    let engine = engine();
+    let (mut state, mut config) = test_state();
+    let regions = CaptureRegions::default();
+    let source = CountingSource {
+        captures: Default::default(),
+    };
+    let captures = || source.captures.load(std::sync::atomic::Ordering::SeqCst);
+    let run_poll = |state: &mut EncounterState, config: &Config| {
+        encounter_process(&engine, state, &source, &regions, config).unwrap();
+    };
+
+    // Out of battle: the strip is new, so both crops are read.
+    run_poll(&mut state, &config);
+    assert_eq!(captures(), 1);
+    // Same strip: skipped without OCR.
+    run_poll(&mut state, &config);
+    assert_eq!(captures(), 2);
+    // In battle: only the names.
+    state.windows.get_mut(&source.id()).unwrap().in_encounter = true;
+    run_poll(&mut state, &config);
+    assert_eq!(captures(), 3);
+    // In battle, watching for the end message: both crops again.
+    config.battle_end_phrases = vec!["got away safely".to_string()];
+    run_poll(&mut state, &config);
+    assert_eq!(captures(), 4);
+}
+
+#[test]
+fn record_encounter_counts_like_detection_and_can_be_undone() {
+    let (mut state, config) = test_state();
+    state.start_session();
+    let found = [("oddish".to_string(), None)];
+    let counted = record_encounter(&mut state, &found, None, false, Utc::now(), &config).unwrap();
+    assert_eq!(counted, 1);
+    assert_eq!(state.encounters, 1);
+    assert_eq!(state.mon_stats["oddish"], 1);
+    assert_eq!(state.last_encounter, vec!["oddish"]);
+    assert_eq!(state.sessions[0].encounters, 1);
+    assert_eq!(state.recent_encounters.len(), 1);
+
+    assert!(undo_last_encounter(&mut state, config.count_unit));
+    assert_eq!(state.encounters, 0);
+    assert!(state.mon_stats.is_empty());
+}
+
+// How OCR reads a horde's name plates: one per line, or a row run together on one line.
+const HORDE_LINES: &[&str] = &[
+    "pidgey lv. 5",
+    "pidgey lv. 4",
+    "pidgey lv. 5",
+    "pidgey lv. 6",
+    "pidgey lv. 5",
+];
+const HORDE_ROW: &[&str] = &["pidgey lv. 5 pidgey lv.4 rattata lv. 3", "oddish lv. 7 pidgey lv. 6"];
+
+#[test]
+fn parse_mons_reads_every_horde_member() {
+    let config = Config::default();
+    assert_eq!(parse_mons(&lines(HORDE_LINES), &config), vec!["pidgey"; 5]);
+    assert_eq!(
+        parse_mons(&lines(HORDE_ROW), &config),
+        vec!["pidgey", "pidgey", "rattata", "oddish", "pidgey"]
+    );
+}
+
+#[test]
+fn horde_mode_counts_the_battle_once_and_every_mon() {
+    let (mut state, mut config) = test_state();
+    config.horde_mode = true;
+    // Settings for singles, which horde mode overrides.
+    config.count_duplicates = false;
+    config.max_mons_per_encounter = 2;
+    assert!(poll(&mut state, &config, Some(WILD), HORDE_LINES));
+    assert_eq!(state.encounters, 1);
+    assert_eq!(state.mon_stats["pidgey"], 5);
+    assert_eq!(state.level_stats["pidgey"].max, 6);
+    poll(&mut state, &config, None, &[]);
+
+    assert!(poll(&mut state, &config, Some(WILD), HORDE_ROW));
+    assert_eq!(state.encounters, 2);
+    assert_eq!(state.mons_seen(), 10);
+    assert_eq!(state.mon_stats["pidgey"], 8);
+
+    assert!(undo_last_encounter(&mut state, config.effective_count_unit()));
+    assert_eq!(state.encounters, 1);
+    assert_eq!(state.mons_seen(), 5);
+}
+
+#[test]
+fn backup_state_keeps_the_newest_copies() {
+    let dir = temp_dir("backups");
+    let path = dir.join("state.json");
+    fs::write(&path, "{}").unwrap();
+    let mut made = vec![];
+    for _ in 0..3 {
+        made.push(backup_state(&path, 2).unwrap());
+        // Backups are named by the millisecond.
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    let mut kept: Vec<_> = fs::read_dir(dir.join(BACKUP_DIR))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    kept.sort();
+    assert_eq!(kept, made[1..]);
+    assert_eq!(fs::read_to_string(&made[2]).unwrap(), "{}");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,66 @@
+use eframe::egui;
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use lineuz_encounter_counter::config::{HotkeyAction, Hotkeys};
+use log::warn;
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver};
+
+// The configured keys registered with the OS, so they work while the game has focus. Each one is
+// held with `global_modifiers`, so plain typing in the game doesn't trigger them.
+pub struct GlobalHotkeys {
+    // Unregisters the keys when dropped, so it lives as long as the app.
+    _manager: GlobalHotKeyManager,
+    actions: Receiver<HotkeyAction>,
+}
+
+impl GlobalHotkeys {
+    // Has to run on the UI thread, whose event loop delivers the key messages on Windows and
+    // macOS. Keys that can't be parsed or are taken by another program are skipped with a warning.
+    pub fn new(ctx: &egui::Context, keys: &Hotkeys) -> Result<Self, Box<dyn Error>> {
+        let manager = GlobalHotKeyManager::new()?;
+        let mut ids = Vec::new();
+        for (name, action) in keys.bindings() {
+            if name.trim().is_empty() {
+                continue;
+            }
+            let combo = format!("{}+{}", keys.global_modifiers, name.trim());
+            match register(&manager, &combo) {
+                Ok(id) => ids.push((id, action)),
+                Err(e) => warn!("Couldn't register global hotkey {}: {}", combo, e),
+            }
+        }
+        if ids.is_empty() {
+            return Err("no global hotkey could be registered".into());
+        }
+
+        // Presses arrive while the window is unfocused or hidden, so wake the UI up to act on
+        // them. Only the press counts, not the release.
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+            if event.state != HotKeyState::Pressed {
+                return;
+            }
+            if let Some((_, action)) = ids.iter().find(|(id, _)| *id == event.id) {
+                let _ = tx.send(*action);
+                ctx.request_repaint();
+            }
+        }));
+        Ok(Self {
+            _manager: manager,
+            actions: rx,
+        })
+    }
+
+    pub fn actions(&self) -> Vec<HotkeyAction> {
+        self.actions.try_iter().collect()
+    }
+}
+
+// Parses a combo like "ctrl+shift+S" and registers it, returning the id its events carry.
+fn register(manager: &GlobalHotKeyManager, combo: &str) -> Result<u32, Box<dyn Error>> {
+    let hotkey: HotKey = combo.parse()?;
+    manager.register(hotkey)?;
+    Ok(hotkey.id())
+}
@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+
+// Language of the app's own labels and buttons. What the game shows is read through
+// `wild_phrases` and friends instead, so the two can differ.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+}
+
+// English text -> German. Texts with `{}` are templates the caller fills in.
+const DE: &[(&str, &str)] = &[
+    ("Encounter Counter", "Begegnungszähler"),
+    ("Couldn't load OCR models", "OCR-Modelle konnten nicht geladen werden"),
+    (
+        "Place text-detection.rten and text-recognition.rten next to the executable.",
+        "Lege text-detection.rten und text-recognition.rten neben die Programmdatei.",
+    ),
+    ("Details", "Details"),
+    ("App State", "Status"),
+    ("Idle", "Bereit"),
+    ("Window minimized", "Fenster minimiert"),
+    ("Unfocused — idle", "Nicht im Vordergrund — wartet"),
+    ("Ongoing", "Läuft"),
+    ("Auto-paused (idle)", "Automatisch pausiert (inaktiv)"),
+    ("Paused", "Pausiert"),
+    ("Quitting", "Wird beendet"),
+    ("Unknown", "Unbekannt"),
+    ("No-save mode: progress isn't saved", "Ohne Speichern: Fortschritt wird nicht gesichert"),
+    ("● No game window", "● Kein Spielfenster"),
+    ("● Seeing text", "● Text erkannt"),
+    ("● Window found, no text", "● Fenster gefunden, kein Text"),
+    ("Can't list windows, retrying", "Fenster nicht auflistbar, neuer Versuch"),
+    ("Start", "Start"),
+    ("Pause", "Pause"),
+    ("Reset", "Zurücksetzen"),
+    ("New Session", "Neue Sitzung"),
+    ("Quit", "Beenden"),
+    ("Hide to Tray", "In den Infobereich"),
+    ("Show Window", "Fenster anzeigen"),
+    ("Always on top", "Immer im Vordergrund"),
+    ("Compact", "Kompakt"),
+    ("Double-click to leave compact mode", "Doppelklick beendet den Kompaktmodus"),
+    ("Undo Last", "Letzte rückgängig"),
+    ("Preview Capture", "Aufnahme-Vorschau"),
+    ("Reload Models", "Modelle neu laden"),
+    ("Export CSV", "CSV exportieren"),
+    ("Export State", "Stand exportieren"),
+    ("Save Summary Image", "Zusammenfassung speichern"),
+    ("Clean Up Stats", "Statistik bereinigen"),
+    ("Import State", "Stand importieren"),
+    ("Species OCR missed", "Von OCR verpasste Art"),
+    ("Add (+)", "Hinzufügen (+)"),
+    ("Mode", "Modus"),
+    ("Count", "Zählen"),
+    ("Detect only", "Nur erkennen"),
+    (
+        "Runs OCR and shows what it reads without changing any counts",
+        "Liest per OCR und zeigt den Text, ohne etwas zu zählen",
+    ),
+    ("Wild region", "Wild-Bereich"),
+    ("Mons region", "Pokémon-Bereich"),
+    ("Hide Preview", "Vorschau ausblenden"),
+    ("Total Encounters", "Begegnungen gesamt"),
+    ("Since target", "Seit Ziel"),
+    ("Session Encounters", "Begegnungen (Sitzung)"),
+    ("Rate", "Rate"),
+    ("{}/hr", "{}/Std."),
+    ("Playtime", "Spielzeit"),
+    ("Unique", "Verschiedene"),
+    ("Shiny Encounters", "Shiny-Begegnungen"),
+    ("Shinies", "Shinies"),
+    ("Last", "Zuletzt"),
+    ("Chain", "Kette"),
+    ("Location", "Ort"),
+    ("OCR hit rate", "OCR-Trefferquote"),
+    ("frames", "Bilder"),
+    ("Stage times", "Zeit pro Schritt"),
+    ("Detected text", "Erkannter Text"),
+    ("Bottom strip (wild: {}):", "Unterer Streifen (wild: {}):"),
+    ("Names region (mons: {}):", "Namensbereich (Pokémon: {}):"),
+    ("yes", "ja"),
+    ("no", "nein"),
+    ("Recent Encounters", "Letzte Begegnungen"),
+    ("Search species", "Art suchen"),
+    ("Show %", "% anzeigen"),
+    ("avg Lv.", "Ø Lv."),
+    ("Top 8 Encounters", "Top 8 Begegnungen"),
+    ("Matching", "Treffer für"),
+    ("No species found.", "Keine Art gefunden."),
+    ("{}s ago", "vor {}s"),
+    ("{}m ago", "vor {}m"),
+    ("{}h ago", "vor {}h"),
+    ("{}d ago", "vor {}d"),
+    ("Encounters since the last {}", "Begegnungen seit dem letzten {}"),
+    ("Encounter Summary", "Begegnungsübersicht"),
+    ("Total encounters: {}", "Begegnungen gesamt: {}"),
+    ("Playtime: {}h {}m", "Spielzeit: {} Std. {} Min."),
+    ("Shinies: {}", "Shinies: {}"),
+    ("Chain: {} x{}", "Kette: {} x{}"),
+    ("Top encounters:", "Häufigste Begegnungen:"),
+    ("Shiny encounter: {}", "Shiny-Begegnung: {}"),
+    ("Target encounter: {}", "Ziel-Begegnung: {}"),
+    ("{} encounters!", "{} Begegnungen!"),
+    ("Exported to {}", "Exportiert nach {}"),
+    ("Export failed", "Export fehlgeschlagen"),
+    ("Saved {}", "Gespeichert: {}"),
+    ("Summary failed", "Zusammenfassung fehlgeschlagen"),
+    ("Import failed", "Import fehlgeschlagen"),
+    ("Imported {}", "Importiert: {}"),
+    ("Couldn't save config", "Konfiguration nicht gespeichert"),
+    ("Unknown species: {}", "Unbekannte Art: {}"),
+    ("Added {}", "{} hinzugefügt"),
+    ("Added {}, but saving failed: {}", "{} hinzugefügt, aber Speichern fehlgeschlagen: {}"),
+    ("Stats are already clean.", "Die Statistik ist schon sauber."),
+    ("Merged {} ({} → {} species)", "Zusammengeführt: {} ({} → {} Arten)"),
+    ("Capture failed — paused", "Aufnahme fehlgeschlagen — pausiert"),
+    (
+        "Counting stopped after an error — paused",
+        "Zählen nach einem Fehler gestoppt — pausiert",
+    ),
+    ("No game window to preview", "Kein Spielfenster für die Vorschau"),
+    ("Preview failed", "Vorschau fehlgeschlagen"),
+    ("Models reloaded.", "Modelle neu geladen."),
+    ("Reload failed", "Neu laden fehlgeschlagen"),
+    ("Settings", "Einstellungen"),
+    ("Save every N encounters", "Alle N Begegnungen speichern"),
+    ("Count duplicates", "Duplikate zählen"),
+    ("Horde mode", "Hordenmodus"),
+    ("Min confidence", "Mindestkonfidenz"),
+    ("Name match distance", "Namens-Toleranz"),
+    ("Poll idle (ms)", "Abfrage bereit (ms)"),
+    ("Poll active (ms)", "Abfrage aktiv (ms)"),
+    ("Poll no window (ms)", "Abfrage ohne Fenster (ms)"),
+    ("Target mons", "Ziel-Pokémon"),
+    ("Wild phrases", "Wild-Phrasen"),
+    ("Webhook URL", "Webhook-URL"),
+    ("Location region", "Ortsbereich"),
+    ("to", "bis"),
+    ("Save Settings", "Einstellungen speichern"),
+    (
+        "Saved. Takes effect the next time counting starts.",
+        "Gespeichert. Gilt ab dem nächsten Start des Zählens.",
+    ),
+    ("Couldn't write config.json: {}", "config.json nicht schreibbar: {}"),
+    ("Couldn't write regions.json: {}", "regions.json nicht schreibbar: {}"),
+    (
+        "Min confidence must be between 0.0 and 1.0.",
+        "Mindestkonfidenz muss zwischen 0.0 und 1.0 liegen.",
+    ),
+    ("Webhook URL must start with https://.", "Webhook-URL muss mit https:// beginnen."),
+    ("Sound file not found: {}.", "Sounddatei nicht gefunden: {}."),
+    ("At least one wild phrase is needed.", "Mindestens eine Wild-Phrase ist nötig."),
+    ("Monitor rect needs a width and height.", "Monitor-Rechteck braucht Breite und Höhe."),
+    ("Debug format must be png, jpeg or webp.", "Debug-Format muss png, jpeg oder webp sein."),
+    ("At least one level marker is needed.", "Mindestens ein Level-Marker ist nötig."),
+    ("{}: ratios must be between 0.0 and 1.0.", "{}: Werte müssen zwischen 0.0 und 1.0 liegen."),
+    ("{}: must start before it ends.", "{}: Anfang muss vor dem Ende liegen."),
+];
+
+impl Lang {
+    // `text` in this language. The English text is the key, and is also what shows when a
+    // translation is missing.
+    pub fn tr(self, text: &'static str) -> &'static str {
+        let table = match self {
+            Lang::En => return text,
+            Lang::De => DE,
+        };
+        table
+            .iter()
+            .find(|(en, _)| *en == text)
+            .map_or(text, |(_, translated)| translated)
+    }
+
+    // `template` in this language with each `{}` replaced by the next of `values`, for texts
+    // with more than one blank.
+    pub fn fill(self, template: &'static str, values: &[&str]) -> String {
+        let mut rest = self.tr(template);
+        let mut filled = String::new();
+        for value in values {
+            let Some((head, tail)) = rest.split_once("{}") else {
+                break;
+            };
+            filled.push_str(head);
+            filled.push_str(value);
+            rest = tail;
+        }
+        filled.push_str(rest);
+        filled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_text_translates_with_english_fallback() {
+        assert_eq!(Lang::En.tr("Total Encounters"), "Total Encounters");
+        assert_eq!(Lang::De.tr("Total Encounters"), "Begegnungen gesamt");
+        assert_eq!(Lang::De.tr("Not translated"), "Not translated");
+        assert_eq!(Lang::De.tr("{}m ago").replace("{}", "5"), "vor 5m");
+    }
+
+    #[test]
+    fn fill_replaces_blanks_in_order() {
+        let filled = Lang::De.fill("Merged {} ({} → {} species)", &["a → b", "3", "2"]);
+        assert_eq!(filled, "Zusammengeführt: a → b (3 → 2 Arten)");
+        assert_eq!(Lang::En.fill("Added {}", &["pidgey"]), "Added pidgey");
+    }
+}
@@ -0,0 +1,31 @@
+// Counting engine behind the egui app: window capture, OCR and the encounter state machine.
+// The app in main.rs is one user of it; anything here can be driven from another tool.
+//
+// A minimal loop looks like the app's worker:
+//
+//     let mut state = load_state().unwrap_or_default();
+//     for window in game_windows(&matcher) {
+//         encounter_process(&engine, &mut state, &WindowSource(window), &regions, &config)?;
+//     }
+//     save_state(&state, false)?;
+//
+// Already OCR'd text can be counted without a window through `count_from_texts`.
+
+pub mod config;
+pub mod encounter;
+pub mod i18n;
+pub mod notify;
+pub mod overlay;
+pub mod server;
+pub mod sound;
+pub mod source;
+pub mod species;
+pub mod summary;
+
+pub use config::Config;
+pub use encounter::{
+    confident_lines, contains_any_phrase, count_from_texts, encounter_process, game_windows,
+    has_shiny, load_regions, load_state, parse_mons, save_state, CaptureRegions, EncounterState,
+    PipelineMode, WindowMatcher,
+};
+pub use source::{FrameSource, ReplaySource, WindowSource};
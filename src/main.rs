@@ -1,19 +1,40 @@
 // Standard library imports.
-use std::{env, error::Error, fs, process, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread, time::Duration};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 // External crate imports.
 use ctrlc;
 use eframe::egui;
+use log::{debug, error, info, warn, LevelFilter};
 #[cfg(unix)]
 use nix::sys::signal::{signal, SigHandler, Signal};
 use once_cell::sync::Lazy;
 use xcap::Window;
 
 // Modules.
-mod encounter;
+mod settings;
+#[cfg(all(windows, feature = "tray"))]
+mod tray;
+#[cfg(feature = "global-hotkeys")]
+mod hotkeys;
+use lineuz_encounter_counter::{config, encounter, i18n, overlay, server, source, species, summary};
+use config::{CaptureMode, Config, HotkeyAction};
+use i18n::Lang;
+use source::{FrameSource, MonitorSource, ReplaySource, WindowSource};
 use encounter::{
-    encounter_process, get_current_working_dir, load_state, save_state, EncounterState, APP_NAME,
-    APP_STATE, STATE_IDLE, STATE_ONGOING, STATE_PAUSE, STATE_QUITTING,
+    capture_backoff, consolidate_stats, encounter_process, export_csv, export_state, get_current_working_dir,
+    contains_any_phrase, import_state, load_regions, load_state, lock_or_recover, parse_mons, save_state, undo_last_encounter, CaptureRegions, EncounterState, PipelineMode, WindowMatcher,
+    APP_STATE, MAX_CAPTURE_FAILURES, STATE_IDLE, STATE_ONGOING, STATE_PAUSE, STATE_QUITTING,
 };
 
 // Crate declarations
@@ -26,22 +47,59 @@ extern crate rten_tensor;
 static SHUTDOWN_FLAG: Lazy<Arc<AtomicBool>> =
     Lazy::new(|| Arc::new(AtomicBool::new(false)));
 
+// Set by SIGHUP to re-read config.json and regions.json without a restart.
+static RELOAD_FLAG: AtomicBool = AtomicBool::new(false);
+
+// Settings handed to a running worker after a reload.
+type ReloadedSettings = Arc<Mutex<Option<(Arc<Config>, Arc<CaptureRegions>)>>>;
+
+// Longest a final save may take before the app exits anyway.
+const SHUTDOWN_SAVE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// How often the worker sends the state without an encounter, so the heartbeat stays current.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Seconds without a captured window (or read text) before the heartbeat changes color.
+const HEARTBEAT_STALE_SECS: i64 = 3;
+
+// How often the UI redraws while nothing new came from the worker.
+const IDLE_REPAINT_INTERVAL: Duration = Duration::from_millis(250);
+
+// Failed window listings in a row before the UI says so. Single failures are just retried.
+const WINDOW_LIST_FAILURES_SHOWN: u32 = 3;
+
+// Species listed on the Save Summary Image card.
+const SUMMARY_TOP_SPECIES: usize = 10;
+
+// Window sizes of the full UI and of compact mode, which only shows the count and chain.
+const WINDOW_SIZE: [f32; 2] = [300.0, 350.0];
+const COMPACT_WINDOW_SIZE: [f32; 2] = [180.0, 70.0];
+
+// Opacity (0-255) of the compact mode background, so the game shows through.
+const COMPACT_BACKGROUND_ALPHA: u8 = 160;
+
 // Global app instance.
 static APP_INSTANCE: Lazy<Arc<Mutex<Option<App>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
 
-fn init_engine() -> Result<ocrs::OcrEngine, Box<dyn Error>> {
-    let (detection_path, recognition_path) = get_path_to_models();
+fn init_engine(
+    models_dir: Option<&str>,
+    config: &Config,
+) -> Result<ocrs::OcrEngine, Box<dyn Error>> {
+    let (detection_path, recognition_path) = get_path_to_models(models_dir)?;
     let (detection_model, recognition_model) = load_rten_model(detection_path, recognition_path)?;
-    create_engine(detection_model, recognition_model)
+    create_engine(detection_model, recognition_model, config)
 }
 
 fn create_engine(
     detection_model: rten::Model,
     recognition_model: rten::Model,
+    config: &Config,
 ) -> Result<ocrs::OcrEngine, Box<dyn Error>> {
     let engine = ocrs::OcrEngine::new(ocrs::OcrEngineParams {
         detection_model: Some(detection_model),
         recognition_model: Some(recognition_model),
+        decode_method: config.ocr_decode_method(),
+        allowed_chars: config.ocr_allowed_chars.clone().filter(|chars| !chars.is_empty()),
         ..Default::default()
     })?;
     Ok(engine)
@@ -58,107 +116,943 @@ fn load_rten_model(
     Ok((detection_model, recognition_model))
 }
 
-fn get_path_to_models() -> (String, String) {
+// An explicit models directory (--models-dir, then MODELS_DIR) must hold both models. Without
+// one, the current directory is tried before the exe's directory.
+fn get_path_to_models(models_dir: Option<&str>) -> Result<(String, String), Box<dyn Error>> {
+    let models_dir = models_dir
+        .map(str::to_string)
+        .or_else(|| std::env::var("MODELS_DIR").ok().filter(|dir| !dir.is_empty()));
+    if let Some(dir) = models_dir {
+        let detection_path = format!("{}/text-detection.rten", dir);
+        let recognition_path = format!("{}/text-recognition.rten", dir);
+        for model in [&detection_path, &recognition_path] {
+            if !Path::new(model).is_file() {
+                return Err(format!("OCR model not found: {}", model).into());
+            }
+        }
+        return Ok((detection_path, recognition_path));
+    }
+
     let (exe_path, path) = get_current_working_dir();
     let detection_path = format!("{}/text-detection.rten", path);
     let detection_path_exe = format!("{}/text-detection.rten", exe_path);
     let recognition_path = format!("{}/text-recognition.rten", path);
     let recognition_path_exe = format!("{}/text-recognition.rten", exe_path);
     match fs::read(&detection_path) {
-        Ok(_) => (detection_path, recognition_path),
-        _ => (detection_path_exe, recognition_path_exe),
+        Ok(_) => Ok((detection_path, recognition_path)),
+        _ => Ok((detection_path_exe, recognition_path_exe)),
     }
 }
 
-fn debug_mode() -> Option<Result<(), Box<dyn Error>>> {
+// Command-line arguments.
+#[derive(Debug, Default)]
+struct Args {
+    debug: bool,
+    window: Option<String>,     // --window <name>: title or app name of the game window
+    headless: bool,             // --headless: count without opening the GUI
+    models_dir: Option<String>, // --models-dir <dir>: where the .rten models live
+    pid: Option<u32>,           // --pid <pid>: match the game window by owning process
+    replay: Option<String>,     // --replay <dir>: run saved screenshots through the counter
+    selftest: bool,             // --selftest: check capture and OCR once, then exit
+    serve: Option<u16>,         // --serve <port>: answer GET /stats and /health on localhost
+    state_file: Option<String>, // --state-file <path>: where progress is saved
+    no_save: bool,              // --no-save: never write the state file, for testing
+    restore: Option<String>,         // --restore <backup>: make a backup the current progress
+    merge: Option<(String, String)>, // --merge <a> <b>: combine two state files into --out
+    out: Option<String>,             // --out <path>: where --merge writes
+}
+
+fn parse_args() -> Result<Args, lexopt::Error> {
+    use lexopt::prelude::*;
+
+    let mut args = Args::default();
+    let mut parser = lexopt::Parser::from_env();
+    while let Some(arg) = parser.next()? {
+        match arg {
+            Value(val) if val == "debug" => args.debug = true,
+            Long("window") => args.window = Some(parser.value()?.string()?),
+            Long("headless") => args.headless = true,
+            Long("replay") => args.replay = Some(parser.value()?.string()?),
+            Long("selftest") => args.selftest = true,
+            Long("pid") => args.pid = Some(parser.value()?.parse()?),
+            Long("models-dir") => args.models_dir = Some(parser.value()?.string()?),
+            Long("serve") => args.serve = Some(parser.value()?.parse()?),
+            Long("state-file") => args.state_file = Some(parser.value()?.string()?),
+            Long("no-save") => args.no_save = true,
+            Long("restore") => args.restore = Some(parser.value()?.string()?),
+            Long("merge") => {
+                let a = parser.value()?.string()?;
+                args.merge = Some((a, parser.value()?.string()?));
+            }
+            Long("out") => args.out = Some(parser.value()?.string()?),
+            _ => return Err(arg.unexpected()),
+        }
+    }
+    Ok(args)
+}
+
+// Info and up by default. The `debug` arg or `"debug": true` in state.json also enables the
+// encounter module's debug output. RUST_LOG overrides both.
+fn init_logging(debug: bool) {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(LevelFilter::Info);
+    if debug {
+        builder.filter_module("lineuz_encounter_counter::encounter", LevelFilter::Debug);
+    }
+    builder.parse_default_env().init();
+}
+
+fn debug_mode(matcher: &WindowMatcher) -> Option<Result<(), Box<dyn Error>>> {
     let (exe_path, path) = get_current_working_dir();
     println!("The current directory is {path} exe path {exe_path}");
-    for window in Window::all().unwrap().iter() {
-        println!("Window: {:?}", (window.app_name(), window.title()));
-        if window.title().to_lowercase() == APP_NAME || window.app_name() == APP_NAME {
-            let img = window.capture_image().unwrap();
-            let _ = img.save("debug.png");
+    let windows = Window::all().unwrap_or_else(|e| {
+        println!("Couldn't list windows: {e}");
+        vec![]
+    });
+    for window in &windows {
+        println!("Window: {:?}", (window.app_name(), window.title(), window.pid()));
+        if encounter::game_exist(window, matcher) {
+            match window.capture_image() {
+                Ok(img) => {
+                    let _ = img.save("debug.png");
+                }
+                Err(e) => println!("Couldn't capture {}: {e}", window.title()),
+            }
         }
     }
+    for (index, monitor) in xcap::Monitor::all().unwrap_or_default().iter().enumerate() {
+        println!("Monitor {index}: {:?}", (monitor.name(), monitor.width(), monitor.height()));
+    }
     Some(Ok(()))
 }
 
+// The game windows worth capturing this poll. Also sets the flags behind the "Window minimized"
+// and "Unfocused — idle" states. Errors when the windows couldn't be listed at all.
+fn window_sources(
+    matcher: &WindowMatcher,
+    config: &Config,
+    minimized: &AtomicBool,
+    unfocused: &AtomicBool,
+) -> Result<Vec<WindowSource>, Box<dyn Error>> {
+    let all_windows = encounter::try_game_windows(matcher)?;
+    let found = !all_windows.is_empty();
+    // A minimized or tiny window would only give empty crops, so skip it.
+    let capturable: Vec<Window> = all_windows
+        .into_iter()
+        .filter(encounter::is_window_capturable)
+        .collect();
+    minimized.store(found && capturable.is_empty(), Ordering::SeqCst);
+    let any_capturable = !capturable.is_empty();
+    let windows: Vec<WindowSource> = capturable
+        .into_iter()
+        .filter(|w| !config.pause_when_unfocused || encounter::window_focused(w))
+        .map(WindowSource)
+        .collect();
+    unfocused.store(any_capturable && windows.is_empty(), Ordering::SeqCst);
+    Ok(windows)
+}
+
+// Worker sleep in short slices, waking early once counting stops so Pause/Reset/Quit can join
+// the worker without stalling the UI.
+fn sleep_while_ongoing(millis: u64) {
+    const SLICE_MS: u64 = 5;
+    let deadline = Instant::now() + Duration::from_millis(millis);
+    while APP_STATE.load(Ordering::SeqCst) == STATE_ONGOING {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(SLICE_MS)));
+    }
+}
+
 pub struct App {
     pub encounter_state: Arc<Mutex<EncounterState>>,
-    engine: Arc<ocrs::OcrEngine>,
+    engine: Option<Arc<ocrs::OcrEngine>>, // None when the models failed to load
+    init_error: Option<String>,           // Why the engine failed to load, shown instead of the counter
+    models_dir: Option<String>,           // --models-dir, for Reload Models
+    regions: Arc<CaptureRegions>,        // Crop regions loaded from regions.json
+    matcher: Arc<WindowMatcher>,         // Which window is the game
+    config: Arc<Config>,                 // Settings loaded from config.json
     last_progress: EncounterState,       // Holds initial progress from state.json
     last_rendered_state: EncounterState, // Used for later live updates
     worker_thread: Option<std::thread::JoinHandle<()>>, // Background worker thread
     worker_rx: Option<std::sync::mpsc::Receiver<EncounterState>>, // Message receiver from worker
+    status_message: Option<(String, Instant)>, // Transient feedback shown under the buttons
+    capture_failed: Arc<AtomicBool>, // Set by the worker when it gives up after repeated capture errors
+    unfocused: Arc<AtomicBool>,      // Set while every game window is skipped for being unfocused
+    minimized: Arc<AtomicBool>,      // Set while every game window is too small to capture
+    window_list_failing: Arc<AtomicBool>, // Set while listing windows keeps failing
+    idle_timeout: Arc<AtomicBool>,   // Set by the worker when nothing was counted for auto_pause_after_secs
+    auto_paused: bool,               // Paused by the idle timeout rather than the user
+    stats_filter: String, // Search box text for the stats list
+    import_path: String,  // File the Import State button reads
+    manual_name: String,  // Species the Add (+) button counts
+    reloaded: ReloadedSettings, // Picked up by a running worker
+    show_percent: bool,   // Stats list shows each species' share of all encounters
+    settings: settings::SettingsPanel, // Editable config, applied on the next worker start
+    preview: Option<(egui::TextureHandle, egui::TextureHandle)>, // Wild and mons crops from "Preview Capture"
+    #[cfg(all(windows, feature = "tray"))]
+    tray: Option<tray::Tray>, // None until the UI starts, or when the icon couldn't be created
+    #[cfg(feature = "global-hotkeys")]
+    global_hotkeys: Option<hotkeys::GlobalHotkeys>, // None when off or they couldn't be registered
 }
 
 impl App {
-    pub fn new() -> Self {
-        let engine = Arc::new(init_engine().unwrap());
+    pub fn new(matcher: WindowMatcher, models_dir: Option<&str>) -> Self {
+        let config = Arc::new(Config::load());
+        encounter::set_backups(config.backup_every, config.backup_keep);
+        let (engine, init_error) = match init_engine(models_dir, &config) {
+            Ok(engine) => (Some(Arc::new(engine)), None),
+            Err(e) => {
+                error!("Couldn't load OCR models: {}", e);
+                (None, Some(e.to_string()))
+            }
+        };
         let state = load_state().unwrap_or_default();
+        let regions = Arc::new(load_regions());
         let encounter_state = Arc::new(Mutex::new(state));
-        let last_progress = encounter_state.lock().unwrap().clone();
+        let last_progress = lock_or_recover(&encounter_state).clone();
         let last_rendered_state = last_progress.clone();
+        overlay::write_overlay(&config, &last_progress);
+        let settings = settings::SettingsPanel::new(&config, &regions);
         APP_STATE.store(STATE_IDLE, Ordering::SeqCst);
         Self {
             encounter_state,
             engine,
+            init_error,
+            models_dir: models_dir.map(str::to_string),
+            regions,
+            matcher: Arc::new(matcher),
+            config,
             last_progress,
             last_rendered_state,
             worker_thread: None,
             worker_rx: None,
+            status_message: None,
+            capture_failed: Arc::new(AtomicBool::new(false)),
+            unfocused: Arc::new(AtomicBool::new(false)),
+            minimized: Arc::new(AtomicBool::new(false)),
+            window_list_failing: Arc::new(AtomicBool::new(false)),
+            idle_timeout: Arc::new(AtomicBool::new(false)),
+            auto_paused: false,
+            stats_filter: String::new(),
+            import_path: String::new(),
+            manual_name: String::new(),
+            reloaded: Arc::new(Mutex::new(None)),
+            show_percent: false,
+            settings,
+            preview: None,
+            #[cfg(all(windows, feature = "tray"))]
+            tray: None,
+            #[cfg(feature = "global-hotkeys")]
+            global_hotkeys: None,
         }
     }
     
+    fn start_counting(&mut self) {
+        if APP_STATE.load(Ordering::SeqCst) == STATE_ONGOING {
+            return;
+        }
+        self.auto_paused = false;
+        {
+            let mut state_lock = lock_or_recover(&self.encounter_state);
+            state_lock.start_session();
+            self.last_rendered_state = state_lock.clone();
+        }
+        APP_STATE.store(STATE_ONGOING, Ordering::SeqCst);
+    }
+
+    fn pause_counting(&mut self) {
+        APP_STATE.store(STATE_PAUSE, Ordering::SeqCst);
+        self.stop_worker();
+        let mut state_lock = lock_or_recover(&self.encounter_state);
+        state_lock.end_session();
+        save_state(&state_lock, false).unwrap_or_default();
+        self.last_rendered_state = state_lock.clone();
+    }
+
+    // Saves a timestamped copy of the state next to state.json.
+    fn export_state_file(&mut self) {
+        let state = lock_or_recover(&self.encounter_state).clone();
+        let (_, dir) = get_current_working_dir();
+        let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let file_name = format!("state_export_{}.json", stamp);
+        let path = Path::new(&dir).join(file_name);
+        let lang = self.config.language;
+        let message = match export_state(&state, &path) {
+            Ok(()) => lang.tr("Exported to {}").replace("{}", &path.display().to_string()),
+            Err(e) => format!("{}: {}", lang.tr("Export failed"), e),
+        };
+        self.status_message = Some((message, Instant::now()));
+    }
+
+    // Raises or lowers the window right away and writes the choice to config.json.
+    fn set_always_on_top(&mut self, ctx: &egui::Context, on: bool) {
+        let level = if on {
+            egui::WindowLevel::AlwaysOnTop
+        } else {
+            egui::WindowLevel::Normal
+        };
+        ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(level));
+        self.settings.set_always_on_top(on);
+        self.save_window_config(Config {
+            always_on_top: on,
+            ..(*self.config).clone()
+        });
+    }
+
+    // Switches between the full UI and the compact overlay, resizing the window to fit.
+    fn set_compact_mode(&mut self, ctx: &egui::Context, on: bool) {
+        let size = if on { COMPACT_WINDOW_SIZE } else { WINDOW_SIZE };
+        ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(!on));
+        ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size.into()));
+        self.settings.set_compact_mode(on);
+        self.save_window_config(Config {
+            compact_mode: on,
+            ..(*self.config).clone()
+        });
+    }
+
+    // Window options are changed from the UI directly rather than through Save Settings.
+    fn save_window_config(&mut self, config: Config) {
+        if let Err(e) = config.save() {
+            let message = format!("{}: {}", config.language.tr("Couldn't save config"), e);
+            self.status_message = Some((message, Instant::now()));
+        }
+        self.config = Arc::new(config);
+    }
+
+    // Count and chain on a see-through background. Dragging moves the window, since it has no
+    // title bar, and a double-click brings the full UI back.
+    fn show_compact(&mut self, ctx: &egui::Context, state: &EncounterState) {
+        let lang = self.config.language;
+        let background = egui::Color32::from_black_alpha(COMPACT_BACKGROUND_ALPHA);
+        let frame = egui::Frame::none().fill(background).inner_margin(8.0);
+        egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+            let response = ui
+                .interact(ui.max_rect(), ui.id().with("compact"), egui::Sense::click_and_drag())
+                .on_hover_text(lang.tr("Double-click to leave compact mode"));
+            if response.drag_started() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
+            }
+            if response.double_clicked() {
+                self.set_compact_mode(ctx, false);
+            }
+            ui.heading(state.encounters.to_string());
+            if let Some(species) = &state.chain_species {
+                let chain = state.current_chain;
+                ui.label(format!("{}: {} x{}", lang.tr("Chain"), species, chain));
+            }
+        });
+    }
+
+    // Renders the stats into a timestamped PNG for sharing.
+    fn save_summary_image(&mut self) {
+        let state = lock_or_recover(&self.encounter_state).clone();
+        let (_, dir) = get_current_working_dir();
+        let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let path = Path::new(&dir).join(format!("summary_{}.png", stamp));
+        let lang = self.config.language;
+        let message = match summary::save_summary(&state, SUMMARY_TOP_SPECIES, lang, &path) {
+            Ok(()) => lang.tr("Saved {}").replace("{}", &path.display().to_string()),
+            Err(e) => format!("{}: {}", lang.tr("Summary failed"), e),
+        };
+        self.status_message = Some((message, Instant::now()));
+    }
+
+    // Replaces the state with the one in `import_path` and saves it. Counting stops first.
+    fn import_state_file(&mut self) {
+        let path = self.import_path.trim().to_string();
+        let lang = self.config.language;
+        let mut imported = match import_state(Path::new(&path)) {
+            Ok(imported) => imported,
+            Err(e) => {
+                let message = format!("{}: {}", lang.tr("Import failed"), e);
+                self.status_message = Some((message, Instant::now()));
+                return;
+            }
+        };
+        APP_STATE.store(STATE_IDLE, Ordering::SeqCst);
+        self.stop_worker();
+        {
+            let mut state_lock = lock_or_recover(&self.encounter_state);
+            imported.debug = state_lock.debug;
+            imported.mode = state_lock.mode;
+            *state_lock = imported.clone();
+            save_state(&state_lock, false).unwrap_or_default();
+            overlay::write_overlay(&self.config, &state_lock);
+        }
+        self.last_rendered_state = imported.clone();
+        self.last_progress = imported;
+        let message = lang.tr("Imported {}").replace("{}", &path);
+        self.status_message = Some((message, Instant::now()));
+    }
+
+    fn reset_counting(&mut self) {
+        APP_STATE.store(STATE_IDLE, Ordering::SeqCst);
+        self.stop_worker();
+        let new_state = EncounterState::default();
+        {
+            let mut state_lock = lock_or_recover(&self.encounter_state);
+            *state_lock = new_state.clone();
+            save_state(&state_lock, false).unwrap_or_default();
+            overlay::write_overlay(&self.config, &state_lock);
+        }
+        self.last_rendered_state = new_state.clone();
+        self.last_progress = new_state;
+    }
+
+    fn new_session(&mut self) {
+        let mut state_lock = lock_or_recover(&self.encounter_state);
+        state_lock.reset_session();
+        save_state(&state_lock, false).unwrap_or_default();
+        overlay::write_overlay(&self.config, &state_lock);
+        self.last_rendered_state = state_lock.clone();
+        self.last_progress = state_lock.clone();
+    }
+
+    fn undo_last(&mut self) {
+        let mut state_lock = lock_or_recover(&self.encounter_state);
+        if undo_last_encounter(&mut state_lock, self.config.effective_count_unit()) {
+            save_state(&state_lock, false).unwrap_or_default();
+            overlay::write_overlay(&self.config, &state_lock);
+            self.last_rendered_state = state_lock.clone();
+            self.last_progress = state_lock.clone();
+        }
+    }
+
+    // Counts one encounter of `manual_name` that OCR missed. The name is snapped to a species
+    // like a detected one, and counted at the current location without a window behind it.
+    fn add_manual(&mut self) {
+        let typed = self.manual_name.trim().to_lowercase();
+        let lang = self.config.language;
+        let Some(mon) = species::normalize_mon_name(&typed, self.config.name_match_distance) else {
+            let message = lang.tr("Unknown species: {}").replace("{}", &typed);
+            self.status_message = Some((message, Instant::now()));
+            return;
+        };
+        let mut state_lock = lock_or_recover(&self.encounter_state);
+        let location = state_lock.current_location.clone();
+        let found = [(mon.clone(), None)];
+        let result = encounter::record_encounter(
+            &mut state_lock,
+            &found,
+            location,
+            false,
+            chrono::Utc::now(),
+            &self.config,
+        );
+        // Undo takes this one back, not the last detected battle.
+        state_lock.last_window = None;
+        let message = match result {
+            Ok(_) => lang.tr("Added {}").replace("{}", &mon),
+            Err(e) => {
+                lang.fill("Added {}, but saving failed: {}", &[mon.as_str(), &e.to_string()])
+            }
+        };
+        self.last_rendered_state = state_lock.clone();
+        self.last_progress = state_lock.clone();
+        drop(state_lock);
+        self.status_message = Some((message, Instant::now()));
+        self.manual_name.clear();
+    }
+
+    // Merges misread species names in the stats and reports what changed.
+    fn clean_up_stats(&mut self) {
+        let cleanup = {
+            let mut state_lock = lock_or_recover(&self.encounter_state);
+            let cleanup = consolidate_stats(&mut state_lock, self.config.name_match_distance);
+            if !cleanup.renamed.is_empty() {
+                save_state(&state_lock, false).unwrap_or_default();
+                overlay::write_overlay(&self.config, &state_lock);
+            }
+            self.last_rendered_state = state_lock.clone();
+            self.last_progress = state_lock.clone();
+            cleanup
+        };
+        let lang = self.config.language;
+        let message = if cleanup.renamed.is_empty() {
+            lang.tr("Stats are already clean.").to_string()
+        } else {
+            let renamed: Vec<String> = cleanup
+                .renamed
+                .iter()
+                .map(|(from, to)| format!("{} → {}", from, to))
+                .collect();
+            lang.fill(
+                "Merged {} ({} → {} species)",
+                &[
+                    renamed.join(", ").as_str(),
+                    &cleanup.species_before.to_string(),
+                    &cleanup.species_after.to_string(),
+                ],
+            )
+        };
+        self.status_message = Some((message, Instant::now()));
+    }
+
+    fn set_mode(&mut self, mode: PipelineMode) {
+        let mut state_lock = lock_or_recover(&self.encounter_state);
+        state_lock.mode = mode;
+        self.last_rendered_state = state_lock.clone();
+        self.last_progress = state_lock.clone();
+    }
+
+    // Raw lines from the last OCR pass, and what the counter makes of them.
+    fn detection_panel(&self, ui: &mut egui::Ui, state: &EncounterState) {
+        let lang = self.config.language;
+        egui::CollapsingHeader::new(lang.tr("Detected text"))
+            .default_open(state.mode == PipelineMode::DetectOnly)
+            .show(ui, |ui| {
+                let phrases = &self.config.wild_phrases;
+                let wild = contains_any_phrase(&state.detected_wild_lines, phrases);
+                let wild = lang.tr(if wild { "yes" } else { "no" });
+                ui.label(lang.tr("Bottom strip (wild: {}):").replace("{}", wild));
+                for line in &state.detected_wild_lines {
+                    ui.monospace(line);
+                }
+                let mons = parse_mons(&state.detected_mon_lines, &self.config);
+                ui.label(lang.tr("Names region (mons: {}):").replace("{}", &mons.join(", ")));
+                for line in &state.detected_mon_lines {
+                    ui.monospace(line);
+                }
+            });
+    }
+
+    fn quit(&mut self) {
+        APP_STATE.store(STATE_QUITTING, Ordering::SeqCst);
+        self.stop_worker();
+        flush_and_exit(&self.encounter_state, 0);
+    }
+
+    // Runs the button actions for the configured keys. Only the initial press counts, so a held
+    // key fires once, and keys are ignored while a text field has focus.
+    fn handle_hotkeys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let pressed: Vec<egui::Key> = ctx.input(|i| {
+            i.events
+                .iter()
+                .filter_map(|event| match event {
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        repeat: false,
+                        ..
+                    } => Some(*key),
+                    _ => None,
+                })
+                .collect()
+        });
+        if pressed.is_empty() {
+            return;
+        }
+        let keys = self.config.hotkeys.clone();
+        let hit = |name: &str| egui::Key::from_name(name).is_some_and(|k| pressed.contains(&k));
+        for (name, action) in keys.bindings() {
+            if hit(name) {
+                self.run_hotkey(ctx, action);
+            }
+        }
+        ctx.request_repaint();
+    }
+
+    fn run_hotkey(&mut self, ctx: &egui::Context, action: HotkeyAction) {
+        match action {
+            HotkeyAction::Start => self.start_counting(),
+            HotkeyAction::Pause => self.pause_counting(),
+            HotkeyAction::Reset => self.reset_counting(),
+            HotkeyAction::NewSession => self.new_session(),
+            HotkeyAction::Undo => self.undo_last(),
+            HotkeyAction::Quit => self.quit(),
+            HotkeyAction::Compact => self.set_compact_mode(ctx, !self.config.compact_mode),
+        }
+    }
+
+    // Registers the system-wide hotkeys when they are turned on. Called once the UI's event loop
+    // exists, like the tray icon.
+    #[cfg(feature = "global-hotkeys")]
+    fn init_global_hotkeys(&mut self, ctx: &egui::Context) {
+        if !self.config.hotkeys.global {
+            return;
+        }
+        match hotkeys::GlobalHotkeys::new(ctx, &self.config.hotkeys) {
+            Ok(global) => self.global_hotkeys = Some(global),
+            Err(e) => warn!("Couldn't register the global hotkeys: {}", e),
+        }
+    }
+
+    // Runs the system-wide hotkeys pressed since the last frame, even while the game has focus.
+    #[cfg(feature = "global-hotkeys")]
+    fn handle_global_hotkeys(&mut self, ctx: &egui::Context) {
+        let actions = self.global_hotkeys.as_ref().map(|keys| keys.actions()).unwrap_or_default();
+        for action in actions {
+            self.run_hotkey(ctx, action);
+            ctx.request_repaint();
+        }
+    }
+
+    // Creates the tray icon. Called once the UI's event loop exists.
+    #[cfg(all(windows, feature = "tray"))]
+    fn init_tray(&mut self, ctx: &egui::Context) {
+        match tray::Tray::new(ctx, self.config.language) {
+            Ok(tray) => self.tray = Some(tray),
+            Err(e) => warn!("Couldn't create the tray icon: {}", e),
+        }
+    }
+
+    // Runs the actions picked from the tray menu, like the buttons would.
+    #[cfg(all(windows, feature = "tray"))]
+    fn handle_tray(&mut self, ctx: &egui::Context) {
+        let actions = self.tray.as_ref().map(|tray| tray.actions()).unwrap_or_default();
+        for action in actions {
+            match action {
+                tray::TrayAction::Show => {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+                }
+                tray::TrayAction::Start => self.start_counting(),
+                tray::TrayAction::Pause => self.pause_counting(),
+                tray::TrayAction::Reset => self.reset_counting(),
+                tray::TrayAction::Quit => self.quit(),
+            }
+            ctx.request_repaint();
+        }
+    }
+
+    // Pauses once the worker has given up on capturing. Returns true when that happened.
+    fn handle_capture_failure(&mut self) -> bool {
+        if !self.capture_failed.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        self.pause_counting();
+        let message = self.config.language.tr("Capture failed — paused").to_string();
+        self.status_message = Some((message, Instant::now()));
+        true
+    }
+
+    // Pauses when the worker died while counting, i.e. a poll panicked, instead of showing a
+    // counter that runs but counts nothing. Returns true when that happened.
+    fn handle_worker_crash(&mut self) -> bool {
+        let crashed = APP_STATE.load(Ordering::SeqCst) == STATE_ONGOING
+            && self.worker_thread.as_ref().is_some_and(|handle| handle.is_finished());
+        if !crashed {
+            return false;
+        }
+        if let Some(handle) = self.worker_thread.take() {
+            if handle.join().is_err() {
+                error!("Worker thread panicked, pausing.");
+            }
+        }
+        self.pause_counting();
+        let message = self.config.language.tr("Counting stopped after an error — paused");
+        let message = message.to_string();
+        self.status_message = Some((message, Instant::now()));
+        true
+    }
+
+    // Re-reads config.json and regions.json after a SIGHUP. A running worker switches to them on
+    // its next poll.
+    fn handle_reload(&mut self) -> bool {
+        if !RELOAD_FLAG.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        self.config = Arc::new(Config::load());
+        encounter::set_backups(self.config.backup_every, self.config.backup_keep);
+        self.regions = Arc::new(load_regions());
+        self.settings = settings::SettingsPanel::new(&self.config, &self.regions);
+        if self.worker_thread.is_some() {
+            *lock_or_recover(&self.reloaded) =
+                Some((Arc::clone(&self.config), Arc::clone(&self.regions)));
+        }
+        info!("Config reloaded");
+        true
+    }
+
+    fn handle_idle_timeout(&mut self) -> bool {
+        if !self.idle_timeout.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        self.pause_counting();
+        self.auto_paused = true;
+        true
+    }
+
+    fn load_preview(&mut self, ctx: &egui::Context) {
+        let lang = self.config.language;
+        let source: Box<dyn FrameSource> = match self.config.capture_mode {
+            CaptureMode::Monitor => {
+                match MonitorSource::new(self.config.monitor_index, self.config.monitor_rect) {
+                    Ok(monitor) => Box::new(monitor),
+                    Err(e) => {
+                        self.preview = None;
+                        let message = format!("{}: {}", lang.tr("Preview failed"), e);
+                        self.status_message = Some((message, Instant::now()));
+                        return;
+                    }
+                }
+            }
+            CaptureMode::Window => match encounter::game_windows(&self.matcher).into_iter().next() {
+                Some(window) => Box::new(WindowSource(window)),
+                None => {
+                    self.preview = None;
+                    let message = lang.tr("No game window to preview").to_string();
+                    self.status_message = Some((message, Instant::now()));
+                    return;
+                }
+            },
+        };
+        match encounter::capture_preview(source.as_ref(), &self.regions, &self.config) {
+            Ok((wild, mons)) => {
+                let texture = |name: &str, img: &image::RgbImage| {
+                    let size = [img.width() as usize, img.height() as usize];
+                    let color_image = egui::ColorImage::from_rgb(size, img.as_raw());
+                    ctx.load_texture(name, color_image, egui::TextureOptions::default())
+                };
+                self.preview = Some((
+                    texture("preview_wild", &wild),
+                    texture("preview_mons", &mons),
+                ));
+            }
+            Err(e) => {
+                self.preview = None;
+                let message = format!("{}: {}", lang.tr("Preview failed"), e);
+                self.status_message = Some((message, Instant::now()));
+            }
+        }
+    }
+
+    // Loads the models again, i.e. after replacing broken files, and swaps them in if they load.
+    fn reload_models(&mut self) {
+        let lang = self.config.language;
+        let message = match init_engine(self.models_dir.as_deref(), &self.config) {
+            Ok(engine) => {
+                // A running worker holds the old engine, so it is stopped here and the update
+                // loop restarts it with the new one.
+                let was_ongoing = APP_STATE.load(Ordering::SeqCst) == STATE_ONGOING;
+                if was_ongoing {
+                    APP_STATE.store(STATE_PAUSE, Ordering::SeqCst);
+                }
+                self.stop_worker();
+                self.engine = Some(Arc::new(engine));
+                self.init_error = None;
+                if was_ongoing {
+                    APP_STATE.store(STATE_ONGOING, Ordering::SeqCst);
+                }
+                info!("OCR models reloaded.");
+                lang.tr("Models reloaded.").to_string()
+            }
+            Err(e) => {
+                error!("Couldn't reload OCR models: {}", e);
+                if self.engine.is_none() {
+                    self.init_error = Some(e.to_string());
+                }
+                format!("{}: {}", lang.tr("Reload failed"), e)
+            }
+        };
+        self.status_message = Some((message, Instant::now()));
+    }
+
+    fn stop_worker(&mut self) {
+        if let Some(handle) = self.worker_thread.take() {
+            handle.join().ok();
+        }
+        self.worker_rx = None;
+    }
+
     fn start_worker(&mut self) {
+        let Some(engine) = &self.engine else {
+            return;
+        };
         if self.worker_thread.is_none() {
             let encounter_state_clone = Arc::clone(&self.encounter_state);
-            let engine_clone = Arc::clone(&self.engine);
+            let engine_clone = Arc::clone(engine);
+            let mut regions_clone = Arc::clone(&self.regions);
+            let matcher_clone = Arc::clone(&self.matcher);
+            let mut config_clone = Arc::clone(&self.config);
+            let reloaded = Arc::clone(&self.reloaded);
+            let capture_failed = Arc::clone(&self.capture_failed);
+            let unfocused = Arc::clone(&self.unfocused);
+            let minimized = Arc::clone(&self.minimized);
+            let window_list_failing = Arc::clone(&self.window_list_failing);
+            let idle_timeout = Arc::clone(&self.idle_timeout);
             let (state_tx, state_rx) = std::sync::mpsc::channel();
             self.worker_rx = Some(state_rx);
     
             self.worker_thread = Some(std::thread::spawn(move || {
                 // Use a dynamic sleep: longer sleep when an encounter is active, shorter when idle.
-                let mut sleep_duration = 50;
+                let mut sleep_duration = config_clone.poll_no_window();
+                let mut failures = 0;
+                let mut list_failures = 0;
+                let mut last_encounter_at = Instant::now();
+                let mut last_sent_at = Instant::now();
+                let monitor = match config_clone.capture_mode {
+                    CaptureMode::Window => None,
+                    CaptureMode::Monitor => match MonitorSource::new(
+                        config_clone.monitor_index,
+                        config_clone.monitor_rect,
+                    ) {
+                        Ok(monitor) => Some(monitor),
+                        Err(e) => {
+                            error!("Couldn't open monitor {}: {e}", config_clone.monitor_index);
+                            capture_failed.store(true, Ordering::SeqCst);
+                            APP_STATE.store(STATE_PAUSE, Ordering::SeqCst);
+                            return;
+                        }
+                    },
+                };
                 while APP_STATE.load(Ordering::SeqCst) == STATE_ONGOING {
-                    if let Some(window) = Window::all()
-                        .ok()
-                        .and_then(|w| w.into_iter().find(|w| encounter::game_exist(w)))
-                    {
-                        if let Ok(mut state) = encounter_state_clone.lock() {
-                            // Operate directly on the shared state.
-                            let encounter_happened =
-                                encounter_process(&engine_clone, &mut *state, &window)
-                                    .unwrap_or(false);
+                    if let Some((config, regions)) = lock_or_recover(&reloaded).take() {
+                        config_clone = config;
+                        regions_clone = regions;
+                    }
+                    if let Some(limit) = config_clone.auto_pause_after() {
+                        if last_encounter_at.elapsed() >= limit {
+                            info!("No encounter for {}s, pausing.", limit.as_secs());
+                            idle_timeout.store(true, Ordering::SeqCst);
+                            APP_STATE.store(STATE_PAUSE, Ordering::SeqCst);
+                            break;
+                        }
+                    }
+                    let windows = if monitor.is_some() {
+                        vec![]
+                    } else {
+                        match window_sources(&matcher_clone, &config_clone, &minimized, &unfocused)
+                        {
+                            Ok(windows) => {
+                                list_failures = 0;
+                                window_list_failing.store(false, Ordering::SeqCst);
+                                windows
+                            }
+                            Err(e) => {
+                                // No window this tick; back off instead of hammering it.
+                                list_failures += 1;
+                                warn!("Couldn't list windows: {e}");
+                                if list_failures >= WINDOW_LIST_FAILURES_SHOWN {
+                                    window_list_failing.store(true, Ordering::SeqCst);
+                                }
+                                sleep_while_ongoing(capture_backoff(list_failures));
+                                continue;
+                            }
+                        }
+                    };
+                    let sources: Vec<&dyn FrameSource> = match &monitor {
+                        Some(monitor) => vec![monitor],
+                        None => windows.iter().map(|w| w as &dyn FrameSource).collect(),
+                    };
+                    if !sources.is_empty() {
+                        {
+                            let mut state = lock_or_recover(&encounter_state_clone);
+                            // Operate directly on the shared state, one game client at a time.
+                            let mut encounter_happened = false;
+                            let mut poll_failed = false;
+                            let location_before = state.current_location.clone();
+                            for source in sources {
+                                match encounter_process(
+                                    &engine_clone,
+                                    &mut state,
+                                    source,
+                                    &regions_clone,
+                                    &config_clone,
+                                ) {
+                                    Ok(happened) => encounter_happened |= happened,
+                                    Err(e) => {
+                                        warn!(
+                                            "Capture failed for {}: {e}",
+                                            source.name()
+                                        );
+                                        poll_failed = true;
+                                    }
+                                }
+                            }
                             if encounter_happened {
+                                last_encounter_at = Instant::now();
+                            }
+                            if encounter_happened
+                                || state.current_location != location_before
+                                || last_sent_at.elapsed() >= HEARTBEAT_INTERVAL
+                            {
+                                last_sent_at = Instant::now();
                                 let _ = state_tx.send(state.clone());
-                                sleep_duration = 100; // Slow down during an active encounter.
+                            }
+                            if poll_failed {
+                                failures += 1;
+                                if failures >= MAX_CAPTURE_FAILURES {
+                                    error!("Capture keeps failing, pausing.");
+                                    capture_failed.store(true, Ordering::SeqCst);
+                                    APP_STATE.store(STATE_PAUSE, Ordering::SeqCst);
+                                    break;
+                                }
+                                sleep_duration = capture_backoff(failures);
+                                drop(state);
+                                sleep_while_ongoing(sleep_duration);
+                                continue;
+                            }
+                            failures = 0;
+                            if encounter_happened {
+                                sleep_duration = config_clone.poll_active(); // Slow down during an active encounter.
                             } else {
-                                sleep_duration = 10; // Poll more frequently when idle.
+                                sleep_duration = config_clone.poll_idle(); // Poll more frequently when idle.
                             }
                         }
                     } else {
-                        sleep_duration = 50;
+                        sleep_duration = config_clone.poll_no_window();
                     }
-                    std::thread::sleep(Duration::from_millis(sleep_duration));
+                    sleep_while_ongoing(sleep_duration);
                 }
-                println!("[DEBUG] Worker thread exiting.");
+                unfocused.store(false, Ordering::SeqCst);
+                minimized.store(false, Ordering::SeqCst);
+                window_list_failing.store(false, Ordering::SeqCst);
+                debug!("Worker thread exiting.");
             }));
         }
     }
 }
 
 impl eframe::App for App {
+    // Only seen behind compact mode's see-through panel; the full UI paints over all of it.
+    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+        [0.0; 4]
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Check if a shutdown has been signaled.
-        if SHUTDOWN_FLAG.load(Ordering::SeqCst) {
-            eprintln!("Shutdown flag set. Saving final state and exiting...");
-            if let Ok(state) = self.encounter_state.lock() {
-                save_state(&state, false).unwrap_or_default();
-            }
-            process::exit(0);
+        let lang = self.config.language;
+        if let Some(init_error) = self.init_error.clone() {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.heading(lang.tr("Couldn't load OCR models"));
+                ui.label(lang.tr(
+                    "Place text-detection.rten and text-recognition.rten next to the executable.",
+                ));
+                ui.label(format!("{}: {}", lang.tr("Details"), init_error));
+                ui.horizontal(|ui| {
+                    if ui.button(lang.tr("Reload Models")).clicked() {
+                        self.reload_models();
+                        ctx.request_repaint();
+                    }
+                    if ui.button(lang.tr("Quit")).clicked() {
+                        process::exit(1);
+                    }
+                });
+            });
+            return;
         }
-    
+
+        if self.handle_capture_failure()
+            || self.handle_worker_crash()
+            || self.handle_idle_timeout()
+            || self.handle_reload()
+        {
+            ctx.request_repaint();
+        }
+
+        self.handle_hotkeys(ctx);
+        #[cfg(feature = "global-hotkeys")]
+        self.handle_global_hotkeys(ctx);
+        #[cfg(all(windows, feature = "tray"))]
+        self.handle_tray(ctx);
+
         // Start the worker thread if in Ongoing state.
         if APP_STATE.load(Ordering::SeqCst) == STATE_ONGOING {
             if self.worker_thread.is_none() {
@@ -166,15 +1060,16 @@ impl eframe::App for App {
             }
         }
     
-        // Process state updates from the worker thread.
-        if let Some(rx) = &self.worker_rx {
-            if let Ok(new_state) = rx.recv_timeout(Duration::from_millis(20)) {
-                println!("[DEBUG] UI received new state update!");
+        // Process state updates from the worker thread. Without one, check back in a while
+        // instead of redrawing every frame, so an idle counter doesn't keep the CPU/GPU busy.
+        match self.worker_rx.as_ref().map(|rx| rx.try_recv()) {
+            Some(Ok(new_state)) => {
+                debug!("UI received new state update!");
                 self.last_rendered_state = new_state;
                 ctx.request_repaint();
-            } else {
-                ctx.request_repaint();
             }
+            Some(Err(_)) => ctx.request_repaint_after(IDLE_REPAINT_INTERVAL),
+            None => {}
         }
     
         let state_copy = if APP_STATE.load(Ordering::SeqCst) == STATE_IDLE {
@@ -182,119 +1077,656 @@ impl eframe::App for App {
         } else {
             self.last_rendered_state.clone()
         };
+        #[cfg(all(windows, feature = "tray"))]
+        if let Some(tray) = &mut self.tray {
+            tray.set_count(state_copy.encounters);
+        }
+        if self.config.compact_mode {
+            self.show_compact(ctx, &state_copy);
+            return;
+        }
     
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Encounter Counter");
+            ui.heading(lang.tr("Encounter Counter"));
             let state_text = match APP_STATE.load(Ordering::SeqCst) {
                 STATE_IDLE => "Idle",
+                STATE_ONGOING if self.minimized.load(Ordering::SeqCst) => "Window minimized",
+                STATE_ONGOING if self.unfocused.load(Ordering::SeqCst) => "Unfocused — idle",
                 STATE_ONGOING => "Ongoing",
+                STATE_PAUSE if self.auto_paused => "Auto-paused (idle)",
                 STATE_PAUSE => "Paused",
                 STATE_QUITTING => "Quitting",
                 _ => "Unknown",
             };
-            ui.label(format!("App State: {}", state_text));
+            ui.label(format!("{}: {}", lang.tr("App State"), lang.tr(state_text)));
+            if encounter::no_save() {
+                let text = lang.tr("No-save mode: progress isn't saved");
+                ui.colored_label(egui::Color32::YELLOW, text);
+            }
+            if APP_STATE.load(Ordering::SeqCst) == STATE_ONGOING {
+                let (color, text) = heartbeat(&state_copy);
+                ui.colored_label(color, lang.tr(text));
+                if self.window_list_failing.load(Ordering::SeqCst) {
+                    let text = lang.tr("Can't list windows, retrying");
+                    ui.colored_label(egui::Color32::RED, text);
+                }
+            }
     
+            let keys = self.config.hotkeys.clone();
             ui.horizontal(|ui| {
-                if ui.button("Start (S)").clicked() {
-                    APP_STATE.store(STATE_ONGOING, Ordering::SeqCst);
+                if ui.button(format!("{} ({})", lang.tr("Start"), keys.start)).clicked() {
+                    self.start_counting();
                 }
     
-                if ui.button("Pause (P)").clicked() {
-                    APP_STATE.store(STATE_PAUSE, Ordering::SeqCst);
-                    if let Some(handle) = self.worker_thread.take() {
-                        handle.join().ok();
-                    }
-                    self.worker_rx = None;
-                    {
-                        let state_lock = self.encounter_state.lock().unwrap();
-                        save_state(&state_lock, false).unwrap_or_default();
-                    }
+                if ui.button(format!("{} ({})", lang.tr("Pause"), keys.pause)).clicked() {
+                    self.pause_counting();
                     ctx.request_repaint();
                 }
     
-                if ui.button("Reset (R)").clicked() {
-                    APP_STATE.store(STATE_IDLE, Ordering::SeqCst);
-                    if let Some(handle) = self.worker_thread.take() {
-                        handle.join().ok();
-                    }
-                    self.worker_rx = None;
-                    let new_state = EncounterState::default();
-                    {
-                        let mut state_lock = self.encounter_state.lock().unwrap();
-                        *state_lock = new_state.clone();
-                    }
-                    {
-                        let state_lock = self.encounter_state.lock().unwrap();
-                        save_state(&state_lock, false).unwrap_or_default();
-                    }
-                    self.last_rendered_state = new_state.clone();
-                    self.last_progress = new_state;
+                if ui.button(format!("{} ({})", lang.tr("Reset"), keys.reset)).clicked() {
+                    self.reset_counting();
                     ctx.request_repaint();
                 }
     
-                if ui.button("Quit (Q)").clicked() {
-                    APP_STATE.store(STATE_QUITTING, Ordering::SeqCst);
-                    if let Some(handle) = self.worker_thread.take() {
-                        handle.join().ok();
-                    }
-                    self.worker_rx = None;
-                    {
-                        let state_lock = self.encounter_state.lock().unwrap();
-                        save_state(&state_lock, false).unwrap_or_default();
-                    }
-                    process::exit(0);
+                let new_session = format!("{} ({})", lang.tr("New Session"), keys.new_session);
+                if ui.button(new_session).clicked() {
+                    self.new_session();
+                    ctx.request_repaint();
+                }
+
+                if ui.button(format!("{} ({})", lang.tr("Quit"), keys.quit)).clicked() {
+                    self.quit();
+                }
+
+                #[cfg(all(windows, feature = "tray"))]
+                if self.tray.is_some() && ui.button(lang.tr("Hide to Tray")).clicked() {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+                }
+
+                let mut on_top = self.config.always_on_top;
+                if ui.checkbox(&mut on_top, lang.tr("Always on top")).changed() {
+                    self.set_always_on_top(ctx, on_top);
+                }
+
+                if ui.button(format!("{} ({})", lang.tr("Compact"), keys.compact)).clicked() {
+                    self.set_compact_mode(ctx, true);
                 }
             });
     
+            ui.horizontal(|ui| {
+                if ui.button(format!("{} ({})", lang.tr("Undo Last"), keys.undo)).clicked() {
+                    self.undo_last();
+                    ctx.request_repaint();
+                }
+
+                if ui.button(lang.tr("Preview Capture")).clicked() {
+                    self.load_preview(ctx);
+                }
+
+                if ui.button(lang.tr("Reload Models")).clicked() {
+                    self.reload_models();
+                    ctx.request_repaint();
+                }
+
+                if ui.button(lang.tr("Export CSV")).clicked() {
+                    let (_, path) = get_current_working_dir();
+                    let csv_path = Path::new(&path).join("encounters.csv");
+                    let message = match export_csv(&state_copy, &csv_path) {
+                        Ok(()) => {
+                            let path = csv_path.display().to_string();
+                            lang.tr("Exported to {}").replace("{}", &path)
+                        }
+                        Err(e) => format!("{}: {}", lang.tr("Export failed"), e),
+                    };
+                    self.status_message = Some((message, Instant::now()));
+                }
+
+                if ui.button(lang.tr("Export State")).clicked() {
+                    self.export_state_file();
+                }
+
+                if ui.button(lang.tr("Save Summary Image")).clicked() {
+                    self.save_summary_image();
+                }
+
+                if ui.button(lang.tr("Clean Up Stats")).clicked() {
+                    self.clean_up_stats();
+                    ctx.request_repaint();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.import_path)
+                        .hint_text("state_export_....json"),
+                );
+                if ui.button(lang.tr("Import State")).clicked() {
+                    self.import_state_file();
+                    ctx.request_repaint();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.manual_name)
+                        .hint_text(lang.tr("Species OCR missed")),
+                );
+                let can_add = !self.manual_name.trim().is_empty();
+                if ui.add_enabled(can_add, egui::Button::new(lang.tr("Add (+)"))).clicked() {
+                    self.add_manual();
+                    ctx.request_repaint();
+                }
+            });
+            let mut mode = state_copy.mode;
+            ui.horizontal(|ui| {
+                ui.label(format!("{}:", lang.tr("Mode")));
+                ui.radio_value(&mut mode, PipelineMode::Count, lang.tr("Count"));
+                ui.radio_value(&mut mode, PipelineMode::DetectOnly, lang.tr("Detect only"))
+                    .on_hover_text(lang.tr(
+                        "Runs OCR and shows what it reads without changing any counts",
+                    ));
+            });
+            if mode != state_copy.mode {
+                self.set_mode(mode);
+                ctx.request_repaint();
+            }
+            if self
+                .status_message
+                .as_ref()
+                .is_some_and(|(_, shown_at)| shown_at.elapsed() >= Duration::from_secs(3))
+            {
+                self.status_message = None;
+            }
+            if let Some((message, _)) = &self.status_message {
+                ui.label(message);
+                ctx.request_repaint_after(Duration::from_millis(500));
+            }
+
+            let mut hide_preview = false;
+            if let Some((wild, mons)) = &self.preview {
+                ui.label(format!("{}:", lang.tr("Wild region")));
+                ui.add(egui::Image::new(wild).shrink_to_fit());
+                ui.label(format!("{}:", lang.tr("Mons region")));
+                ui.add(egui::Image::new(mons).shrink_to_fit());
+                hide_preview = ui.button(lang.tr("Hide Preview")).clicked();
+            }
+            if hide_preview {
+                self.preview = None;
+            }
+
+            if let Some((config, regions)) = self.settings.show(ui, lang) {
+                encounter::set_backups(config.backup_every, config.backup_keep);
+                self.config = Arc::new(config);
+                self.regions = Arc::new(regions);
+            }
+
             ui.separator();
-            ui.label(format!("Total Encounters: {}", state_copy.encounters));
-            ui.label(format!("Last Encounters: {}", state_copy.last_encounter.join(", ")));
+            ui.label(format!("{}: {}", lang.tr("Total Encounters"), state_copy.encounters));
+            if let Some(target) = self.config.target_species.as_deref().filter(|t| !t.is_empty()) {
+                let since = state_copy.encounters_since_target;
+                ui.heading(format!("{}: {}", lang.tr("Since target"), since))
+                    .on_hover_text(lang.tr("Encounters since the last {}").replace("{}", target));
+            }
+            if state_copy.windows.len() > 1 {
+                let mut windows: Vec<_> = state_copy.windows.values().collect();
+                windows.sort_by(|a, b| a.title.cmp(&b.title));
+                for win in windows {
+                    ui.label(format!("  {}: {}", win.title, win.encounters));
+                }
+            }
+            let session_encounters = state_copy.current_session().map_or(0, |s| s.encounters);
+            ui.label(format!("{}: {}", lang.tr("Session Encounters"), session_encounters));
+            // Smoothed once there are two counts; the plain session average until then.
+            let rate = state_copy
+                .smoothed_rate()
+                .or_else(|| state_copy.current_session().and_then(|s| s.encounters_per_hour()));
+            let rate = match rate {
+                Some(rate) => lang.tr("{}/hr").replace("{}", &format!("{:.0}", rate)),
+                None => "—".to_string(),
+            };
+            ui.label(format!("{}: {}", lang.tr("Rate"), rate));
+            let playtime_mins = state_copy.playtime_secs() / 60;
+            ui.label(format!(
+                "{}: {}h {}m",
+                lang.tr("Playtime"),
+                playtime_mins / 60,
+                playtime_mins % 60
+            ));
+            let unique = state_copy.mon_stats.len();
+            match self.config.dex_total.filter(|total| *total > 0) {
+                Some(total) => ui.label(format!(
+                    "{}: {} / {} ({:.1}%)",
+                    lang.tr("Unique"),
+                    unique,
+                    total,
+                    unique as f64 * 100.0 / total as f64
+                )),
+                None => ui.label(format!("{}: {}", lang.tr("Unique"), unique)),
+            };
+            ui.label(format!("{}: {}", lang.tr("Shiny Encounters"), state_copy.shiny_encounters));
+            if !state_copy.shiny_mons.is_empty() {
+                ui.label(format!("{}: {}", lang.tr("Shinies"), state_copy.shiny_mons.join(", ")));
+            }
+            match state_copy.last_encounter_time {
+                Some(time) => {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", lang.tr("Last")));
+                        mon_labels(ui, &state_copy.last_encounter, &state_copy, &self.config);
+                        ui.label(format!("({})", format_ago(chrono::Utc::now() - time, lang)));
+                    });
+                    ctx.request_repaint_after(Duration::from_secs(1));
+                }
+                None => {
+                    ui.label(format!("{}: —", lang.tr("Last")));
+                }
+            }
+            if let Some(species) = &state_copy.chain_species {
+                let chain = state_copy.current_chain;
+                ui.label(format!("{}: {} x{}", lang.tr("Chain"), species, chain));
+            }
+            ui.label(format!(
+                "{}: {}",
+                lang.tr("Location"),
+                state_copy.current_location.as_deref().unwrap_or("—")
+            ));
+            if state_copy.debug || state_copy.mode == PipelineMode::DetectOnly {
+                self.detection_panel(ui, &state_copy);
+            }
+            if state_copy.debug {
+                if let Some(rate) = state_copy.ocr_hit_rate() {
+                    ui.label(format!(
+                        "{}: {:.1}% ({} {})",
+                        lang.tr("OCR hit rate"),
+                        rate,
+                        state_copy.frames_processed,
+                        lang.tr("frames")
+                    ));
+                }
+                if let Some(average) = &state_copy.frame_timings.average {
+                    ui.collapsing(lang.tr("Stage times"), |ui| {
+                        for (stage, time) in average.stages() {
+                            ui.label(format!("{}: {:.1} ms", stage, time.as_secs_f64() * 1000.0));
+                        }
+                    });
+                }
+            }
+            if !state_copy.recent_encounters.is_empty() {
+                ui.collapsing(lang.tr("Recent Encounters"), |ui| {
+                    egui::ScrollArea::vertical()
+                        .id_salt("recent_encounters")
+                        .max_height(120.0)
+                        .show(ui, |ui| {
+                            for recent in state_copy.recent_encounters.iter().rev() {
+                                let time = recent.at.with_timezone(&chrono::Local);
+                                ui.horizontal(|ui| {
+                                    ui.label(time.format("%H:%M:%S").to_string());
+                                    mon_labels(ui, &recent.mons, &state_copy, &self.config);
+                                    if let Some(location) = &recent.location {
+                                        ui.label(format!("@ {}", location));
+                                    }
+                                });
+                            }
+                        });
+                });
+            }
             ui.separator();
     
-            ui.heading("Top 8 Encounters");
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.stats_filter)
+                        .hint_text(lang.tr("Search species")),
+                );
+                ui.checkbox(&mut self.show_percent, lang.tr("Show %"));
+            });
+            let show_percent = self.show_percent;
+            let format_count =
+                |count: u32| format_stat(count, state_copy.mons_seen(), show_percent);
+            // Average level read for the species, when the names region shows levels.
+            let format_level = |mon: &str| match state_copy.level_stats.get(mon) {
+                Some(levels) => levels
+                    .average()
+                    .map(|avg| format!(", {} {:.1}", lang.tr("avg Lv."), avg))
+                    .unwrap_or_default(),
+                None => String::new(),
+            };
+            let query = self.stats_filter.trim().to_lowercase();
             let mut top_encounters: Vec<(&String, &u32)> = state_copy.mon_stats.iter().collect();
-            top_encounters.sort_by(|a, b| b.1.cmp(a.1));
-            for (i, (mon, count)) in top_encounters.iter().take(8).enumerate() {
-                ui.label(format!("{}. {} - {}", i + 1, mon, count));
+            top_encounters.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            if query.is_empty() {
+                ui.heading(lang.tr("Top 8 Encounters"));
+                for (i, (mon, count)) in top_encounters.iter().take(8).enumerate() {
+                    let level = format_level(mon);
+                    ui.label(format!("{}. {} - {}{}", i + 1, mon, format_count(**count), level));
+                }
+            } else {
+                top_encounters.retain(|(mon, _)| mon.to_lowercase().contains(&query));
+                ui.heading(format!("{} \"{}\"", lang.tr("Matching"), query));
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (mon, count) in &top_encounters {
+                        let level = format_level(mon);
+                        ui.label(format!("{} - {}{}", mon, format_count(**count), level));
+                    }
+                    if top_encounters.is_empty() {
+                        ui.label(lang.tr("No species found."));
+                    }
+                });
             }
         });
     }
 }
 
-impl Drop for App {
-    fn drop(&mut self) {
-        let state_clone = Arc::clone(&self.encounter_state);
-        let save_thread = std::thread::spawn(move || {
-            if let Ok(state) = state_clone.lock() {
-                if state.unsaved_encounters > 0 {
-                    println!("[DEBUG] Saving unsaved encounters before exit...");
-                    save_state(&state, false).unwrap_or_default();
+// Whether the worker is seeing the game: green when OCR read text lately, yellow when a window
+// is captured but nothing is read (i.e. wrong regions), red when there is no window to capture.
+fn heartbeat(state: &EncounterState) -> (egui::Color32, &'static str) {
+    let now = chrono::Utc::now();
+    let recent = |at: Option<chrono::DateTime<chrono::Utc>>| {
+        at.is_some_and(|at| (now - at).num_seconds() < HEARTBEAT_STALE_SECS)
+    };
+    if !recent(state.last_window_at) {
+        (egui::Color32::RED, "● No game window")
+    } else if recent(state.last_text_at) {
+        (egui::Color32::GREEN, "● Seeing text")
+    } else {
+        (egui::Color32::YELLOW, "● Window found, no text")
+    }
+}
+
+// Gray for species that make up a big share of all encounters, gold for rare ones, the normal
+// text color in between. Thresholds are `rarity_common_percent` and `rarity_rare_percent`.
+fn rarity_color(mon: &str, state: &EncounterState, config: &Config) -> Option<egui::Color32> {
+    let seen = state.mons_seen();
+    if seen == 0 {
+        return None;
+    }
+    let count = state.mon_stats.get(mon).copied().unwrap_or(0);
+    let percent = count as f32 * 100.0 / seen as f32;
+    if percent >= config.rarity_common_percent {
+        Some(egui::Color32::GRAY)
+    } else if percent <= config.rarity_rare_percent {
+        Some(egui::Color32::GOLD)
+    } else {
+        None
+    }
+}
+
+// One label per species, colored by rarity.
+fn mon_labels(ui: &mut egui::Ui, mons: &[String], state: &EncounterState, config: &Config) {
+    for mon in mons {
+        match rarity_color(mon, state, config) {
+            Some(color) => ui.colored_label(color, mon),
+            None => ui.label(mon),
+        };
+    }
+}
+
+// A stats count, with its share of all encounters when asked, i.e. "230 (18.4%)".
+fn format_stat(count: u32, total: u32, show_percent: bool) -> String {
+    if show_percent && total > 0 {
+        format!("{} ({:.1}%)", count, count as f64 * 100.0 / total as f64)
+    } else {
+        count.to_string()
+    }
+}
+
+// Short relative time, i.e. "3s ago", "5m ago", "2h ago".
+fn format_ago(elapsed: chrono::Duration, lang: Lang) -> String {
+    let secs = elapsed.num_seconds().max(0);
+    let (template, value) = if secs < 60 {
+        ("{}s ago", secs)
+    } else if secs < 3600 {
+        ("{}m ago", secs / 60)
+    } else if secs < 86400 {
+        ("{}h ago", secs / 3600)
+    } else {
+        ("{}d ago", secs / 86400)
+    };
+    lang.tr(template).replace("{}", &value.to_string())
+}
+
+// Feeds saved full-window screenshots through encounter_process in filename order, for
+// debugging detection without the game. Nothing is saved and no webhook, event log or overlay
+// is written.
+fn run_replay(dir: &Path, models_dir: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let regions = load_regions();
+    let config = Config {
+        webhook_url: None,
+        event_log: false,
+        overlay_file: None,
+        // Frames are not taken in real time, so the time-based debounce does not apply.
+        min_encounter_gap_ms: 0,
+        ..Config::load()
+    };
+    let engine = init_engine(models_dir, &config)?;
+    let source = ReplaySource::new(dir)?;
+    let mut state = EncounterState {
+        dry_run: true,
+        ..EncounterState::default()
+    };
+    println!("Replaying {} frames from {}", source.frame_count(), dir.display());
+    while source.remaining() > 0 {
+        let frame = source.frame_count() - source.remaining() + 1;
+        match encounter_process(&engine, &mut state, &source, &regions, &config) {
+            Ok(true) => println!("Frame {}: {}", frame, state.last_encounter.join(", ")),
+            Ok(false) => {}
+            Err(e) => println!("Frame {}: error: {}", frame, e),
+        }
+    }
+    println!("Encounters: {}", state.encounters);
+    let mut stats: Vec<(&String, &u32)> = state.mon_stats.iter().collect();
+    stats.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    for (mon, count) in stats {
+        println!("  {} - {}", mon, count);
+    }
+    Ok(())
+}
+
+// Replaces the current progress with `backup`. The file it replaces becomes the state file's
+// .bak, like on any save, so a wrong pick can still be undone.
+fn run_restore(backup: &Path) -> Result<(), Box<dyn Error>> {
+    // --no-save writes nothing, so the restore would only pretend to happen.
+    if encounter::no_save() {
+        return Err("--restore can't be combined with --no-save".into());
+    }
+    let state = import_state(backup)?;
+    save_state(&state, false)?;
+    println!(
+        "Restored {} into {}: {} encounters",
+        backup.display(),
+        encounter::state_file().display(),
+        state.encounters
+    );
+    Ok(())
+}
+
+// Combines two state files (i.e. from two machines) into `out`. Neither input is changed.
+fn run_merge(a: &Path, b: &Path, out: &Path) -> Result<(), Box<dyn Error>> {
+    let merged = encounter::merge_states(import_state(a)?, import_state(b)?);
+    export_state(&merged, out)?;
+    println!(
+        "Merged into {}: {} encounters, {} species, {} sessions",
+        out.display(),
+        merged.encounters,
+        merged.mon_stats.len(),
+        merged.sessions.len()
+    );
+    Ok(())
+}
+
+// Captures once and OCRs both regions, printing what worked, so a new setup can be checked
+// without starting a hunt. Fails when OCR read no text at all.
+fn run_selftest(matcher: &WindowMatcher, models_dir: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let regions = load_regions();
+    let config = Config::load();
+    let yes_no = |ok: bool| if ok { "yes" } else { "no" };
+
+    let engine = init_engine(models_dir, &config)?;
+    println!("OCR models: loaded");
+    let source: Box<dyn FrameSource> = match config.capture_mode {
+        CaptureMode::Monitor => {
+            Box::new(MonitorSource::new(config.monitor_index, config.monitor_rect)?)
+        }
+        CaptureMode::Window => match encounter::game_windows(matcher).into_iter().next() {
+            Some(window) => Box::new(WindowSource(window)),
+            None => {
+                println!("Game window: not found");
+                return Err("no game window; start the game or pass --window/--pid".into());
+            }
+        },
+    };
+    println!("Capturing: {}", source.name());
+    let frame = source.capture()?;
+    println!("Dimensions: {}x{}", frame.width(), frame.height());
+
+    let (wild, mons) = encounter::preview_crops(&frame, &regions, &config)?;
+    let wild_lines = encounter::confident_lines(&engine, wild, config.min_confidence)?;
+    let mon_lines = encounter::confident_lines(&engine, mons, config.min_confidence)?;
+    println!("Wild region: {} lines", wild_lines.len());
+    for line in &wild_lines {
+        println!("  {}", line);
+    }
+    println!("Mons region: {} lines", mon_lines.len());
+    for line in &mon_lines {
+        println!("  {}", line);
+    }
+    let has_marker = mon_lines.iter().any(|line| {
+        let line = line.to_lowercase();
+        config.level_markers.iter().any(|marker| line.contains(&marker.to_lowercase()))
+    });
+    let wild = contains_any_phrase(&wild_lines, &config.wild_phrases);
+    println!("Wild phrase matched: {}", yes_no(wild));
+    println!("Level marker matched: {}", yes_no(has_marker));
+    println!("Pokemon read: {}", parse_mons(&mon_lines, &config).join(", "));
+
+    if wild_lines.is_empty() && mon_lines.is_empty() {
+        return Err("OCR read no text; check the regions with Preview Capture".into());
+    }
+    println!("Capture and OCR work.");
+    Ok(())
+}
+
+// Counts without a GUI. Commands are read from stdin: s = start, p = pause, q = quit.
+fn run_headless(mut app: App) -> Result<(), Box<dyn Error>> {
+    if let Some(init_error) = app.init_error.take() {
+        return Err(format!("Couldn't load OCR models: {}", init_error).into());
+    }
+    println!("Headless mode. Commands: s = start, p = pause, q = quit.");
+    let (cmd_tx, cmd_rx) = std::sync::mpsc::channel::<String>();
+    thread::spawn(move || {
+        for line in std::io::stdin().lines().map_while(Result::ok) {
+            if cmd_tx.send(line.trim().to_lowercase()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut printed_encounters = app.last_progress.encounters;
+    while !SHUTDOWN_FLAG.load(Ordering::SeqCst) {
+        if app.handle_capture_failure() {
+            println!("Capture failed — paused. Type s to retry.");
+        }
+        if app.handle_worker_crash() {
+            println!("Counting stopped after an error — paused. Type s to restart.");
+        }
+        app.handle_reload();
+        if app.handle_idle_timeout() {
+            println!("Auto-paused (idle). Type s to resume.");
+        }
+        if let Ok(cmd) = cmd_rx.try_recv() {
+            match cmd.as_str() {
+                "s" => {
+                    app.start_counting();
+                    app.start_worker();
+                    println!("Counting started.");
+                }
+                "p" => {
+                    app.pause_counting();
+                    println!("Counting paused.");
                 }
+                "q" => break,
+                _ => println!("Unknown command: {}", cmd),
             }
-        });
-        let _ = save_thread.join();
+        }
+
+        if let Some(rx) = &app.worker_rx {
+            if let Ok(new_state) = rx.recv_timeout(Duration::from_millis(100)) {
+                // The worker also sends heartbeats, so only print when the count moved.
+                if new_state.encounters != printed_encounters {
+                    printed_encounters = new_state.encounters;
+                    println!(
+                        "Encounters: {} | Last: {}",
+                        new_state.encounters,
+                        new_state.last_encounter.join(", ")
+                    );
+                }
+            }
+        } else {
+            thread::sleep(Duration::from_millis(100));
+        }
     }
+
+    APP_STATE.store(STATE_QUITTING, Ordering::SeqCst);
+    app.stop_worker();
+    encounter::flush_state(&app.encounter_state, SHUTDOWN_SAVE_TIMEOUT, false)?;
+    println!("Progress saved. Bye.");
+    Ok(())
+}
+
+// The one way out once counting may have happened: saves (bounded by SHUTDOWN_SAVE_TIMEOUT),
+// logs whether that worked and exits with `code`.
+fn flush_and_exit(state: &Arc<Mutex<EncounterState>>, code: i32) -> ! {
+    APP_STATE.store(STATE_QUITTING, Ordering::SeqCst);
+    match encounter::flush_state(state, SHUTDOWN_SAVE_TIMEOUT, false) {
+        Ok(()) => info!("Progress saved."),
+        Err(e) => error!("Couldn't save progress before exiting: {}", e),
+    }
+    process::exit(code);
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let is_debug = env::args().find(|arg| arg == "debug");
-    if is_debug.is_some() {
-        if let Some(value) = debug_mode() {
+    let args = parse_args()?;
+    if let Some(path) = &args.state_file {
+        encounter::set_state_file(PathBuf::from(path));
+    }
+    encounter::set_no_save(args.no_save);
+    init_logging(args.debug || encounter::saved_debug_flag());
+    if let Some(backup) = &args.restore {
+        return run_restore(Path::new(backup));
+    }
+    if let Some((a, b)) = &args.merge {
+        let out = args.out.as_deref().ok_or("--merge needs --out <file>")?;
+        return run_merge(Path::new(a), Path::new(b), Path::new(out));
+    }
+    let matcher = WindowMatcher::new(args.window, args.pid);
+    if args.selftest {
+        return run_selftest(&matcher, args.models_dir.as_deref());
+    }
+    if let Some(dir) = &args.replay {
+        return run_replay(Path::new(dir), args.models_dir.as_deref());
+    }
+    if args.debug {
+        if let Some(value) = debug_mode(&matcher) {
             return value;
         }
     }
-    let app = App::new();
-    *APP_INSTANCE.lock().unwrap() = Some(app); // Store the app instance globally
+    let app = App::new(matcher.clone(), args.models_dir.as_deref());
+    if let Some(port) = args.serve {
+        if let Err(e) = server::start(port, Arc::clone(&app.encounter_state)) {
+            error!("{}", e);
+            process::exit(1);
+        }
+    }
+    let shared_state = Arc::clone(&app.encounter_state);
+    let monitor_mode = app.config.capture_mode == CaptureMode::Monitor;
+    *lock_or_recover(&APP_INSTANCE) = Some(app); // Store the app instance globally
     
-    // Spawn a thread to monitor the shutdown flag.
-    {
+    // Spawn a thread to monitor the shutdown flag. Headless mode saves on its own before exiting.
+    if !args.headless {
         let shutdown_flag = Arc::clone(&SHUTDOWN_FLAG);
+        let state = Arc::clone(&shared_state);
         std::thread::spawn(move || {
             while !shutdown_flag.load(Ordering::SeqCst) {
                 thread::sleep(Duration::from_millis(100));
             }
-            eprintln!("Shutdown flag detected. Exiting application.");
-            process::exit(0);
+            info!("Shutdown flag detected. Exiting application.");
+            flush_and_exit(&state, 0);
         });
     }
     
@@ -302,7 +1734,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     ctrlc::set_handler({
         let shutdown_flag = Arc::clone(&SHUTDOWN_FLAG);
         move || {
-            eprintln!("Received Ctrl+C! Signaling shutdown...");
+            info!("Received Ctrl+C! Signaling shutdown...");
             shutdown_flag.store(true, Ordering::SeqCst);
         }
     })
@@ -311,55 +1743,69 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Handle SIGTERM (Linux/macOS) with graceful shutdown similar to Ctrl+C.
     #[cfg(unix)]
     unsafe {
+        // Only the flag is set here; the save happens on the thread watching it.
         extern "C" fn handle_sigterm(_: i32) {
-            eprintln!("Received SIGTERM! Signaling shutdown...");
             SHUTDOWN_FLAG.store(true, Ordering::SeqCst);
-            // Brief delay to help with final I/O.
-            thread::sleep(Duration::from_millis(100));
-            process::exit(0);
         }
         signal(Signal::SIGTERM, SigHandler::Handler(handle_sigterm))
             .expect("Failed to set SIGTERM handler");
+
+        // SIGHUP reloads the settings; the app picks the flag up on its next frame/loop.
+        extern "C" fn handle_sighup(_: i32) {
+            RELOAD_FLAG.store(true, Ordering::SeqCst);
+        }
+        signal(Signal::SIGHUP, SigHandler::Handler(handle_sighup))
+            .expect("Failed to set SIGHUP handler");
+    }
+
+    if args.headless {
+        let app = lock_or_recover(&APP_INSTANCE).take().unwrap();
+        return run_headless(app);
     }
     
-    if let Some(_window) = Window::all()
-        .ok()
-        .and_then(|w| w.into_iter().find(|w| encounter::game_exist(w)))
-    {
+    if monitor_mode || !encounter::game_windows(&matcher).is_empty() {
+        let (on_top, compact, lang) = lock_or_recover(&APP_INSTANCE).as_ref().map_or(
+            (false, false, Lang::default()),
+            |app| (app.config.always_on_top, app.config.compact_mode, app.config.language),
+        );
+        // Transparent from the start, since compact mode can be switched on at any time.
+        let mut viewport = egui::ViewportBuilder::default()
+            .with_inner_size(if compact { COMPACT_WINDOW_SIZE } else { WINDOW_SIZE })
+            .with_decorations(!compact)
+            .with_transparent(true);
+        if on_top {
+            viewport = viewport.with_always_on_top();
+        }
         let native_options = eframe::NativeOptions {
-            viewport: egui::ViewportBuilder::default().with_inner_size([300.0, 350.0]),
+            viewport,
             ..Default::default()
         };
-        std::panic::set_hook(Box::new(|info| {
-            eprintln!("[ERROR] Unexpected crash: {:?}", info);
-            let app_guard = APP_INSTANCE.lock().unwrap();
-            if let Some(ref app) = *app_guard {
-                if let Ok(state) = app.encounter_state.lock() {
-                    save_state(&state, false).unwrap_or_default();
-                    eprintln!("[ERROR] Saved progress before crash.");
-                }
+        let crash_state = Arc::clone(&shared_state);
+        std::panic::set_hook(Box::new(move |info| {
+            error!("Unexpected crash: {:?}", info);
+            match encounter::crash_save(&crash_state, SHUTDOWN_SAVE_TIMEOUT) {
+                Ok(()) => error!("Saved progress before crash."),
+                Err(e) => error!("Couldn't save progress before crash: {}", e),
             }
         }));
         eframe::run_native(
-            "Encounter Counter",
+            lang.tr("Encounter Counter"),
             native_options,
-            Box::new(|_cc| Ok(Box::new(APP_INSTANCE.lock().unwrap().take().unwrap()))),
+            Box::new(|_cc| {
+                #[allow(unused_mut)]
+                let mut app = lock_or_recover(&APP_INSTANCE).take().unwrap();
+                #[cfg(all(windows, feature = "tray"))]
+                app.init_tray(&_cc.egui_ctx);
+                #[cfg(feature = "global-hotkeys")]
+                app.init_global_hotkeys(&_cc.egui_ctx);
+                Ok(Box::new(app))
+            }),
         )?;
     
-        // After run_native returns, perform a final save if shutdown was signaled.
-        if SHUTDOWN_FLAG.load(Ordering::SeqCst) {
-            eprintln!("Shutdown signal detected. Performing final save...");
-            if let Some(app) = APP_INSTANCE.lock().unwrap().take() {
-                if let Ok(state) = app.encounter_state.lock() {
-                    save_state(&state, false).unwrap_or_default();
-                }
-            }
-            thread::sleep(Duration::from_millis(200));
-        }
-    
-        Ok(())
+        // The window was closed.
+        flush_and_exit(&shared_state, 0)
     } else {
-        eprintln!("{} game not found", APP_NAME);
+        error!("{} game not found", matcher.describe());
         process::exit(1);
     }
 }
@@ -0,0 +1,89 @@
+use crate::config::Config;
+use crate::i18n::Lang;
+use log::{debug, warn};
+use notify_rust::Notification;
+use std::thread;
+
+// Detected mons that are on the configured target list. Targets are matched however they were
+// typed, i.e. " Pidgey" finds "pidgey".
+pub fn matching_targets(config: &Config, mons: &[String]) -> Vec<String> {
+    mons.iter()
+        .filter(|mon| config.target_mons.iter().any(|t| t.trim().to_lowercase() == **mon))
+        .cloned()
+        .collect()
+}
+
+// Posts to the Discord webhook on a target or shiny encounter. Runs on its own thread so a
+// slow or failing request never holds up OCR.
+pub fn send_webhook(config: &Config, mons: &[String], shiny: bool) {
+    let url = match &config.webhook_url {
+        Some(url) if !url.is_empty() => url.clone(),
+        _ => return,
+    };
+    let targets = matching_targets(config, mons);
+    if targets.is_empty() && !shiny {
+        return;
+    }
+
+    let lang = config.language;
+    let content = if shiny {
+        lang.tr("Shiny encounter: {}").replace("{}", &mons.join(", "))
+    } else {
+        lang.tr("Target encounter: {}").replace("{}", &targets.join(", "))
+    };
+    let body = serde_json::json!({ "content": content }).to_string();
+    thread::spawn(move || {
+        match ureq::post(&url)
+            .set("Content-Type", "application/json")
+            .send_string(&body)
+        {
+            Ok(_) => debug!("Webhook sent."),
+            Err(e) => warn!("Webhook failed: {e}"),
+        }
+    });
+}
+
+// Desktop notification for crossing an encounter milestone. Own thread for the same reason as
+// the webhook.
+pub fn send_milestone(milestone: u32, lang: Lang) {
+    thread::spawn(move || {
+        match Notification::new()
+            .summary(lang.tr("Encounter Counter"))
+            .body(&lang.tr("{} encounters!").replace("{}", &milestone.to_string()))
+            .show()
+        {
+            Ok(_) => debug!("Milestone notification shown."),
+            Err(e) => warn!("Milestone notification failed: {e}"),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_targets(targets: &[&str]) -> Config {
+        Config {
+            target_mons: targets.iter().map(|target| target.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn mons(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn targets_match_regardless_of_case_and_spacing() {
+        let config = with_targets(&["Pidgey", " ODDISH "]);
+        let found = matching_targets(&config, &mons(&["pidgey", "rattata", "oddish"]));
+        assert_eq!(found, ["pidgey", "oddish"]);
+    }
+
+    #[test]
+    fn no_target_in_the_battle_matches_nothing() {
+        let encounter = mons(&["rattata", "zubat"]);
+        assert!(matching_targets(&with_targets(&["pidgey"]), &encounter).is_empty());
+        assert!(matching_targets(&with_targets(&[]), &encounter).is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+use crate::config::Config;
+use crate::encounter::EncounterState;
+use log::warn;
+use std::fs;
+
+// Fills the overlay template. Placeholders: {count}, {session}, {chain}, {chain_species},
+// {since_target}, {last} and {shinies}.
+pub fn render_overlay(template: &str, state: &EncounterState) -> String {
+    let session = state.current_session().map_or(0, |s| s.encounters);
+    template
+        .replace("{count}", &state.encounters.to_string())
+        .replace("{session}", &session.to_string())
+        .replace("{chain}", &state.current_chain.to_string())
+        .replace("{chain_species}", state.chain_species.as_deref().unwrap_or(""))
+        .replace("{since_target}", &state.encounters_since_target.to_string())
+        .replace("{last}", &state.last_encounter.join(", "))
+        .replace("{shinies}", &state.shiny_encounters.to_string())
+}
+
+// Rewrites the overlay text file, for an OBS Text source set to read from file.
+pub fn write_overlay(config: &Config, state: &EncounterState) {
+    let path = match &config.overlay_file {
+        Some(path) if !path.is_empty() => path,
+        _ => return,
+    };
+    if let Err(e) = fs::write(path, render_overlay(&config.overlay_format, state)) {
+        warn!("Failed to write overlay file {path}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encounter::Session;
+
+    fn hunting_state() -> EncounterState {
+        EncounterState {
+            encounters: 120,
+            current_chain: 7,
+            chain_species: Some("pidgey".to_string()),
+            encounters_since_target: 42,
+            last_encounter: vec!["pidgey".to_string(), "rattata".to_string()],
+            shiny_encounters: 1,
+            sessions: vec![Session {
+                encounters: 15,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn render_overlay_fills_every_placeholder() {
+        let template = "{count} {session} {chain} {chain_species} {since_target} {last} {shinies}";
+        let text = render_overlay(template, &hunting_state());
+        assert_eq!(text, "120 15 7 pidgey 42 pidgey, rattata 1");
+    }
+
+    #[test]
+    fn render_overlay_leaves_other_text_alone() {
+        let template = "Encounters: {count} | Chain: {chain_species}{unknown}";
+        let text = render_overlay(template, &EncounterState::default());
+        assert_eq!(text, "Encounters: 0 | Chain: {unknown}");
+    }
+
+    #[test]
+    fn write_overlay_writes_the_rendered_text() {
+        let path = std::env::temp_dir().join(format!("lineuz_{}_overlay.txt", std::process::id()));
+        let config = Config {
+            overlay_file: Some(path.to_string_lossy().into_owned()),
+            overlay_format: "Encounters: {count} | Chain: {chain}".to_string(),
+            ..Default::default()
+        };
+        write_overlay(&config, &hunting_state());
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written, "Encounters: 120 | Chain: 7");
+    }
+}
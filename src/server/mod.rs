@@ -0,0 +1,110 @@
+use crate::encounter::{self, EncounterState};
+use log::{debug, info, warn};
+use std::error::Error;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Requests are handled one at a time, so a client that connects and goes quiet is dropped
+// after this instead of blocking everyone else.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Serves the live state on localhost for dashboards: `GET /stats` is the `EncounterState` as
+// JSON, `GET /health` answers while the app runs. Errors only when the port can't be bound.
+pub fn start(port: u16, state: Arc<Mutex<EncounterState>>) -> Result<(), Box<dyn Error>> {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == ErrorKind::AddrInUse => {
+            return Err(format!("Port {port} is already in use, pick another with --serve").into())
+        }
+        Err(e) => return Err(format!("Couldn't serve on port {port}: {e}").into()),
+    };
+    info!("Serving stats on http://127.0.0.1:{port}/stats");
+    serve(listener, state);
+    Ok(())
+}
+
+// Answers requests on `listener` from a background thread, for as long as the app runs.
+fn serve(listener: TcpListener, state: Arc<Mutex<EncounterState>>) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle(stream, &state) {
+                        debug!("Stats request failed: {e}");
+                    }
+                }
+                Err(e) => warn!("Stats connection failed: {e}"),
+            }
+        }
+    });
+}
+
+fn handle(mut stream: TcpStream, state: &Mutex<EncounterState>) -> Result<(), Box<dyn Error>> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let (status, body) = match (method, path) {
+        ("GET", "/stats") => {
+            let state = encounter::lock_or_recover(state).clone();
+            ("200 OK", serde_json::to_string(&state)?)
+        }
+        ("GET", "/health") => ("200 OK", r#"{"status":"ok"}"#.to_string()),
+        ("GET", _) => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+        _ => ("405 Method Not Allowed", r#"{"error":"method not allowed"}"#.to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    // Sends one request to a server on an ephemeral port and returns the whole response.
+    fn request(state: EncounterState, request_line: &str) -> String {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        serve(listener, Arc::new(Mutex::new(state)));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT)).unwrap();
+        // Only the line the server reads, so nothing is left unread when it closes.
+        write!(stream, "{request_line}\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn stats_returns_the_state_as_json() {
+        let state = EncounterState {
+            encounters: 321,
+            ..Default::default()
+        };
+        let response = request(state, "GET /stats HTTP/1.1");
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["encounters"], 321);
+    }
+
+    #[test]
+    fn unknown_paths_and_methods_are_refused() {
+        let response = request(EncounterState::default(), "GET /nope HTTP/1.1");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"), "{response}");
+        let response = request(EncounterState::default(), "POST /stats HTTP/1.1");
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed"), "{response}");
+    }
+}
@@ -0,0 +1,168 @@
+use lineuz_encounter_counter::config::Config;
+use lineuz_encounter_counter::encounter::{save_regions, CaptureRegion, CaptureRegions};
+use lineuz_encounter_counter::i18n::Lang;
+use eframe::egui;
+
+// Editable copy of the settings behind the collapsible Settings section. Nothing changes for
+// the counter until Save Settings is clicked, and the worker only picks it up on its next start.
+pub struct SettingsPanel {
+    config: Config,
+    regions: CaptureRegions,
+    target_mons: String, // Comma separated, split on save
+    wild_phrases: String, // Comma separated, split on save
+    webhook_url: String, // Empty means no webhook
+    errors: Vec<String>,
+    saved: bool,
+}
+
+impl SettingsPanel {
+    pub fn new(config: &Config, regions: &CaptureRegions) -> Self {
+        Self {
+            config: config.clone(),
+            regions: regions.clone(),
+            target_mons: config.target_mons.join(", "),
+            wild_phrases: config.wild_phrases.join(", "),
+            webhook_url: config.webhook_url.clone().unwrap_or_default(),
+            errors: vec![],
+            saved: false,
+        }
+    }
+
+    // Draws the settings. Returns the new settings once they were validated and written.
+    pub fn show(&mut self, ui: &mut egui::Ui, lang: Lang) -> Option<(Config, CaptureRegions)> {
+        let mut result = None;
+        ui.collapsing(lang.tr("Settings"), |ui| {
+            egui::Grid::new("settings_grid").num_columns(2).show(ui, |ui| {
+                ui.label(lang.tr("Save every N encounters"));
+                ui.add(egui::DragValue::new(&mut self.config.save_interval).range(0..=1000));
+                ui.end_row();
+
+                ui.label(lang.tr("Count duplicates"));
+                ui.checkbox(&mut self.config.count_duplicates, "");
+                ui.end_row();
+
+                ui.label(lang.tr("Horde mode"));
+                ui.checkbox(&mut self.config.horde_mode, "");
+                ui.end_row();
+
+                ui.label(lang.tr("Min confidence"));
+                ui.add(egui::Slider::new(&mut self.config.min_confidence, 0.0..=1.0));
+                ui.end_row();
+
+                ui.label(lang.tr("Name match distance"));
+                ui.add(egui::DragValue::new(&mut self.config.name_match_distance).range(0..=5));
+                ui.end_row();
+
+                ui.label(lang.tr("Poll idle (ms)"));
+                ui.add(egui::DragValue::new(&mut self.config.poll_idle_ms).range(5..=5000));
+                ui.end_row();
+
+                ui.label(lang.tr("Poll active (ms)"));
+                ui.add(egui::DragValue::new(&mut self.config.poll_active_ms).range(5..=5000));
+                ui.end_row();
+
+                ui.label(lang.tr("Poll no window (ms)"));
+                ui.add(egui::DragValue::new(&mut self.config.poll_no_window_ms).range(5..=5000));
+                ui.end_row();
+
+                ui.label(lang.tr("Target mons"));
+                ui.text_edit_singleline(&mut self.target_mons);
+                ui.end_row();
+
+                ui.label(lang.tr("Wild phrases"));
+                ui.text_edit_singleline(&mut self.wild_phrases);
+                ui.end_row();
+
+                ui.label(lang.tr("Webhook URL"));
+                ui.text_edit_singleline(&mut self.webhook_url);
+                ui.end_row();
+            });
+
+            region_editor(ui, lang, "Wild region", &mut self.regions.wild);
+            region_editor(ui, lang, "Mons region", &mut self.regions.mons);
+            region_editor(ui, lang, "Location region", &mut self.regions.location);
+
+            if ui.button(lang.tr("Save Settings")).clicked() {
+                result = self.save(lang);
+            }
+            for error in &self.errors {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            if self.saved {
+                ui.label(lang.tr("Saved. Takes effect the next time counting starts."));
+            }
+        });
+        result
+    }
+
+    // Keeps a setting changed outside the panel from being undone by the next Save Settings.
+    pub fn set_always_on_top(&mut self, on: bool) {
+        self.config.always_on_top = on;
+    }
+
+    pub fn set_compact_mode(&mut self, on: bool) {
+        self.config.compact_mode = on;
+    }
+
+    fn save(&mut self, lang: Lang) -> Option<(Config, CaptureRegions)> {
+        self.saved = false;
+        self.config.target_mons = self
+            .target_mons
+            .split(',')
+            .map(|mon| mon.trim().to_lowercase())
+            .filter(|mon| !mon.is_empty())
+            .collect();
+        self.config.wild_phrases = self
+            .wild_phrases
+            .split(',')
+            .map(|phrase| phrase.trim().to_lowercase())
+            .filter(|phrase| !phrase.is_empty())
+            .collect();
+        let url = self.webhook_url.trim();
+        self.config.webhook_url = (!url.is_empty()).then(|| url.to_string());
+
+        self.errors = self.config.validate();
+        for (name, region) in [
+            ("Wild region", &self.regions.wild),
+            ("Mons region", &self.regions.mons),
+            ("Location region", &self.regions.location),
+        ] {
+            self.errors.extend(region.validate(lang.tr(name), lang));
+        }
+        if !self.errors.is_empty() {
+            return None;
+        }
+        if let Err(e) = self.config.save() {
+            let error = lang.tr("Couldn't write config.json: {}");
+            self.errors.push(error.replace("{}", &e.to_string()));
+            return None;
+        }
+        if let Err(e) = save_regions(&self.regions) {
+            let error = lang.tr("Couldn't write regions.json: {}");
+            self.errors.push(error.replace("{}", &e.to_string()));
+            return None;
+        }
+        self.saved = true;
+        Some((self.config.clone(), self.regions.clone()))
+    }
+}
+
+fn region_editor(
+    ui: &mut egui::Ui,
+    lang: Lang,
+    name: &'static str,
+    region: &mut CaptureRegion,
+) {
+    ui.label(lang.tr(name));
+    ui.horizontal(|ui| {
+        for (label, value) in [
+            ("x", &mut region.start_x),
+            (lang.tr("to"), &mut region.end_x),
+            ("y", &mut region.start_y),
+            (lang.tr("to"), &mut region.end_y),
+        ] {
+            ui.label(label);
+            ui.add(egui::DragValue::new(value).speed(0.01).range(0.0..=1.0));
+        }
+    });
+}
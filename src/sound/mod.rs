@@ -0,0 +1,72 @@
+use crate::config::Config;
+use crate::notify;
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use rodio::{Decoder, OutputStream, Sink};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+// Files to play, handed to the audio thread. The output stream can't leave the thread that
+// opened it, so one long-lived thread owns it and plays whatever arrives.
+static PLAYER: Lazy<Sender<PathBuf>> = Lazy::new(|| {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || play_loop(rx));
+    tx
+});
+
+// Plays the configured sound for a counted encounter without waiting for it to finish. Shinies
+// and targets use `sound_on_target` when it is set.
+pub fn play_encounter(config: &Config, mons: &[String], shiny: bool) {
+    let target = shiny
+        || !notify::matching_targets(config, mons).is_empty()
+        || config
+            .target_species
+            .as_deref()
+            .is_some_and(|target| mons.iter().any(|mon| mon.eq_ignore_ascii_case(target.trim())));
+    let sound = config.sound_on_encounter.as_deref().filter(|path| !path.is_empty());
+    let target_sound = config.sound_on_target.as_deref().filter(|path| !path.is_empty());
+    let path = match (target_sound, sound) {
+        (Some(path), _) if target => path,
+        (_, Some(path)) => path,
+        _ => return,
+    };
+    // Fails only when the audio thread gave up, which it already logged.
+    let _ = PLAYER.send(PathBuf::from(path));
+}
+
+fn play_loop(rx: Receiver<PathBuf>) {
+    let (_stream, handle) = match OutputStream::try_default() {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("No audio output, encounter sounds are off: {e}");
+            return;
+        }
+    };
+    // A file that failed once is skipped from then on, instead of warning on every encounter.
+    let mut broken = HashSet::new();
+    for path in rx {
+        if broken.contains(&path) {
+            continue;
+        }
+        let played = File::open(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| Decoder::new(BufReader::new(file)).map_err(|e| e.to_string()))
+            .and_then(|source| {
+                let sink = Sink::try_new(&handle).map_err(|e| e.to_string())?;
+                sink.append(source);
+                sink.detach();
+                Ok(())
+            });
+        match played {
+            Ok(()) => debug!("Playing {}.", path.display()),
+            Err(e) => {
+                warn!("Can't play {}, turning it off: {e}", path.display());
+                broken.insert(path);
+            }
+        }
+    }
+}
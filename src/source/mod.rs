@@ -0,0 +1,165 @@
+use image::RgbaImage;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use xcap::{Monitor, Window};
+
+// Where encounter_process gets its frames: a live game window, or saved screenshots.
+pub trait FrameSource {
+    // Stable key for the per-window detection state.
+    fn id(&self) -> u32;
+    fn name(&self) -> String;
+    fn capture(&self) -> Result<RgbaImage, Box<dyn Error>>;
+}
+
+pub struct WindowSource(pub Window);
+
+impl FrameSource for WindowSource {
+    fn id(&self) -> u32 {
+        self.0.id()
+    }
+
+    fn name(&self) -> String {
+        self.0.title().to_string()
+    }
+
+    fn capture(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        Ok(self.0.capture_image()?)
+    }
+}
+
+// A fixed part of a monitor, for when the compositor hands xcap a black game window.
+pub struct MonitorSource {
+    monitor: Monitor,
+    index: usize,
+    rect: Option<[u32; 4]>, // x, y, width, height in monitor pixels; None is the whole monitor
+}
+
+impl MonitorSource {
+    pub fn new(index: usize, rect: Option<[u32; 4]>) -> Result<Self, Box<dyn Error>> {
+        let monitor = Monitor::all()?
+            .into_iter()
+            .nth(index)
+            .ok_or_else(|| format!("no monitor {index}, see `debug` for the list"))?;
+        Ok(Self {
+            monitor,
+            index,
+            rect,
+        })
+    }
+}
+
+impl FrameSource for MonitorSource {
+    // Kept clear of window ids, which are small numbers in practice.
+    fn id(&self) -> u32 {
+        u32::MAX - self.index as u32
+    }
+
+    fn name(&self) -> String {
+        self.monitor.name().to_string()
+    }
+
+    fn capture(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        let image = self.monitor.capture_image()?;
+        match self.rect {
+            Some(rect) => crop_to_rect(&image, rect),
+            None => Ok(image),
+        }
+    }
+}
+
+// The `[x, y, width, height]` part of a monitor capture. The sums are checked, so a rect near
+// u32::MAX is reported as outside instead of overflowing.
+fn crop_to_rect(
+    image: &RgbaImage,
+    [x, y, width, height]: [u32; 4],
+) -> Result<RgbaImage, Box<dyn Error>> {
+    let outside =
+        |start: u32, len: u32, max: u32| start.checked_add(len).is_none_or(|end| end > max);
+    if outside(x, width, image.width()) || outside(y, height, image.height()) {
+        return Err(format!(
+            "monitor_rect {x},{y} {width}x{height} is outside the {}x{} monitor",
+            image.width(),
+            image.height()
+        )
+        .into());
+    }
+    Ok(image::imageops::crop_imm(image, x, y, width, height).to_image())
+}
+
+// Full-window PNGs from a directory, one per capture, in filename order.
+pub struct ReplaySource {
+    name: String,
+    frames: Vec<PathBuf>,
+    next: AtomicUsize,
+}
+
+impl ReplaySource {
+    pub fn new(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut frames: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+            })
+            .collect();
+        if frames.is_empty() {
+            return Err(format!("no .png files in {}", dir.display()).into());
+        }
+        frames.sort();
+        Ok(Self {
+            name: dir.display().to_string(),
+            frames,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.frames.len().saturating_sub(self.next.load(Ordering::SeqCst))
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+impl FrameSource for ReplaySource {
+    fn id(&self) -> u32 {
+        0
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn capture(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        let index = self.next.fetch_add(1, Ordering::SeqCst);
+        let path = self
+            .frames
+            .get(index)
+            .ok_or("replay has no frames left")?;
+        Ok(image::open(path)?.to_rgba8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monitor_rect_crops_inside_the_monitor() {
+        let image = RgbaImage::new(100, 50);
+        let crop = crop_to_rect(&image, [10, 5, 90, 45]).unwrap();
+        assert_eq!(crop.dimensions(), (90, 45));
+    }
+
+    #[test]
+    fn monitor_rect_outside_the_monitor_is_an_error() {
+        let image = RgbaImage::new(100, 50);
+        assert!(crop_to_rect(&image, [10, 5, 91, 45]).is_err());
+        // Would wrap around to a small end with unchecked addition.
+        assert!(crop_to_rect(&image, [u32::MAX, 0, 2, 10]).is_err());
+        assert!(crop_to_rect(&image, [0, 1, 10, u32::MAX]).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use once_cell::sync::Lazy;
+
+// Lowercased species names available in PokeMMO (Gen 1-5), one per line.
+static SPECIES: Lazy<Vec<&'static str>> = Lazy::new(|| {
+    include_str!("species.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect()
+});
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+// Snaps an OCR'd name to the closest known species, i.e. "pidqey" -> "pidgey".
+// Returns None when no species is within `max_distance` edits.
+pub fn normalize_mon_name(raw: &str, max_distance: usize) -> Option<String> {
+    closest_species(raw, max_distance).map(|(species, _)| species)
+}
+
+// Like `normalize_mon_name`, also returning how many edits the match took.
+pub fn closest_species(raw: &str, max_distance: usize) -> Option<(String, usize)> {
+    let raw = raw.trim().to_lowercase();
+    if SPECIES.contains(&raw.as_str()) {
+        return Some((raw, 0));
+    }
+    SPECIES
+        .iter()
+        .map(|species| (levenshtein(&raw, species), *species))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(distance, species)| (species.to_string(), distance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snaps_misreads_to_the_closest_species() {
+        assert_eq!(normalize_mon_name("pidqey", 2), Some("pidgey".to_string()));
+        assert_eq!(closest_species(" Pidgey ", 2), Some(("pidgey".to_string(), 0)));
+        assert_eq!(closest_species("pidqey", 2), Some(("pidgey".to_string(), 1)));
+    }
+
+    #[test]
+    fn too_distant_names_are_dropped() {
+        assert_eq!(normalize_mon_name("pidqey", 0), None);
+        assert_eq!(normalize_mon_name("zzzzzzzz", 2), None);
+    }
+
+    #[test]
+    fn empty_names_are_dropped() {
+        assert_eq!(normalize_mon_name("", 2), None);
+        assert_eq!(normalize_mon_name("   ", 2), None);
+    }
+}
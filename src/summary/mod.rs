@@ -0,0 +1,143 @@
+use crate::encounter::EncounterState;
+use crate::i18n::Lang;
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::{Rgb, RgbImage};
+use std::error::Error;
+use std::path::Path;
+
+// The font egui already ships, so the card looks like the app and needs no extra files.
+const FONT: &[u8] = epaint_default_fonts::UBUNTU_LIGHT;
+const WIDTH: u32 = 480;
+const MARGIN: f32 = 24.0;
+const TITLE_SIZE: f32 = 30.0;
+const TEXT_SIZE: f32 = 20.0;
+const LINE_HEIGHT: f32 = 28.0;
+const BACKGROUND: Rgb<u8> = Rgb([30, 30, 36]);
+const TITLE_COLOR: Rgb<u8> = Rgb([255, 215, 0]);
+const TEXT_COLOR: Rgb<u8> = Rgb([230, 230, 230]);
+
+// Text of the summary card below the title, in `lang`: totals first, then the `top` most seen
+// species.
+pub fn summary_lines(state: &EncounterState, top: usize, lang: Lang) -> Vec<String> {
+    let playtime_mins = state.playtime_secs() / 60;
+    let (hours, mins) = ((playtime_mins / 60).to_string(), (playtime_mins % 60).to_string());
+    let mut lines = vec![
+        lang.tr("Total encounters: {}").replace("{}", &state.encounters.to_string()),
+        lang.fill("Playtime: {}h {}m", &[&hours, &mins]),
+        lang.tr("Shinies: {}").replace("{}", &state.shiny_encounters.to_string()),
+    ];
+    if let Some(species) = &state.chain_species {
+        let chain = state.current_chain.to_string();
+        lines.push(lang.fill("Chain: {} x{}", &[species, &chain]));
+    }
+
+    let mut stats: Vec<_> = state.mon_stats.iter().collect();
+    stats.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    if !stats.is_empty() {
+        lines.push(String::new());
+        lines.push(lang.tr("Top encounters:").to_string());
+    }
+    let seen = state.mons_seen().max(1) as f64;
+    for (mon, count) in stats.into_iter().take(top) {
+        let percent = *count as f64 * 100.0 / seen;
+        lines.push(format!("  {} {} ({:.1}%)", mon, count, percent));
+    }
+    lines
+}
+
+// Draws the summary card. The height grows with the number of lines.
+pub fn render_summary(
+    state: &EncounterState,
+    top: usize,
+    lang: Lang,
+) -> Result<RgbImage, Box<dyn Error>> {
+    let font = FontRef::try_from_slice(FONT)?;
+    let lines = summary_lines(state, top, lang);
+    let height = (MARGIN * 2.0 + TITLE_SIZE + 12.0 + LINE_HEIGHT * lines.len() as f32) as u32;
+    let mut img = RgbImage::from_pixel(WIDTH, height, BACKGROUND);
+
+    let title = lang.tr("Encounter Summary");
+    draw_text(&mut img, &font, TITLE_SIZE, (MARGIN, MARGIN), title, TITLE_COLOR);
+    let mut y = MARGIN + TITLE_SIZE + 12.0;
+    for line in &lines {
+        draw_text(&mut img, &font, TEXT_SIZE, (MARGIN, y), line, TEXT_COLOR);
+        y += LINE_HEIGHT;
+    }
+    Ok(img)
+}
+
+pub fn save_summary(
+    state: &EncounterState,
+    top: usize,
+    lang: Lang,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    render_summary(state, top, lang)?.save(path)?;
+    Ok(())
+}
+
+// Blends one line of text onto `img` with its top-left corner at `at`.
+fn draw_text(
+    img: &mut RgbImage,
+    font: &FontRef,
+    size: f32,
+    at: (f32, f32),
+    text: &str,
+    color: Rgb<u8>,
+) {
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+    let mut caret = at.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(caret, at.1 + scaled.ascent()));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue; // Whitespace has no outline
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            if px < 0 || py < 0 || px >= img.width() as i64 || py >= img.height() as i64 {
+                return;
+            }
+            let pixel = img.get_pixel_mut(px as u32, py as u32);
+            for (channel, target) in pixel.0.iter_mut().zip(color.0) {
+                *channel = (*channel as f32 * (1.0 - coverage) + target as f32 * coverage) as u8;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn summary_lists_top_species_by_count() {
+        let state = EncounterState {
+            encounters: 10,
+            mon_stats: HashMap::from([
+                ("pidgey".to_string(), 6),
+                ("rattata".to_string(), 3),
+                ("abra".to_string(), 1),
+            ]),
+            ..Default::default()
+        };
+        let lines = summary_lines(&state, 2, Lang::En);
+        assert_eq!(lines[0], "Total encounters: 10");
+        assert_eq!(&lines[lines.len() - 2..], ["  pidgey 6 (60.0%)", "  rattata 3 (30.0%)"]);
+        assert_eq!(summary_lines(&state, 2, Lang::De)[0], "Begegnungen gesamt: 10");
+
+        let img = render_summary(&state, 2, Lang::De).unwrap();
+        assert!(img.pixels().any(|pixel| *pixel != BACKGROUND));
+    }
+}
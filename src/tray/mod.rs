@@ -0,0 +1,90 @@
+use eframe::egui;
+use lineuz_encounter_counter::i18n::Lang;
+use log::warn;
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver};
+use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+// What a tray menu item asks the app to do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrayAction {
+    Show,
+    Start,
+    Pause,
+    Reset,
+    Quit,
+}
+
+// Tray icon with the count in its tooltip, so the window can be hidden while hunting.
+pub struct Tray {
+    icon: TrayIcon,
+    actions: Receiver<TrayAction>,
+    tooltip: String,
+    lang: Lang,
+}
+
+impl Tray {
+    // Has to run on the UI thread, whose event loop delivers the icon's messages.
+    pub fn new(ctx: &egui::Context, lang: Lang) -> Result<Self, Box<dyn Error>> {
+        let items = [
+            (MenuItem::new(lang.tr("Show Window"), true, None), TrayAction::Show),
+            (MenuItem::new(lang.tr("Start"), true, None), TrayAction::Start),
+            (MenuItem::new(lang.tr("Pause"), true, None), TrayAction::Pause),
+            (MenuItem::new(lang.tr("Reset"), true, None), TrayAction::Reset),
+            (MenuItem::new(lang.tr("Quit"), true, None), TrayAction::Quit),
+        ];
+        let menu = Menu::new();
+        for (item, _) in &items {
+            menu.append(item)?;
+        }
+        let ids: Vec<_> = items
+            .iter()
+            .map(|(item, action)| (item.id().clone(), *action))
+            .collect();
+
+        // Clicks arrive even while the window is hidden, so wake the UI up to act on them.
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+            if let Some((_, action)) = ids.iter().find(|(id, _)| *id == event.id) {
+                let _ = tx.send(*action);
+                ctx.request_repaint();
+            }
+        }));
+
+        let icon = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip(lang.tr("Encounter Counter"))
+            .with_icon(icon()?)
+            .build()?;
+        Ok(Self {
+            icon,
+            actions: rx,
+            tooltip: String::new(),
+            lang,
+        })
+    }
+
+    pub fn actions(&self) -> Vec<TrayAction> {
+        self.actions.try_iter().collect()
+    }
+
+    pub fn set_count(&mut self, encounters: u32) {
+        let tooltip = format!("{}: {}", self.lang.tr("Encounter Counter"), encounters);
+        if tooltip == self.tooltip {
+            return;
+        }
+        if let Err(e) = self.icon.set_tooltip(Some(&tooltip)) {
+            warn!("Couldn't update the tray tooltip: {e}");
+        }
+        self.tooltip = tooltip;
+    }
+}
+
+// A plain gold square, since the app ships no image files to use.
+fn icon() -> Result<Icon, Box<dyn Error>> {
+    const SIZE: u32 = 32;
+    let rgba = [255, 215, 0, 255].repeat((SIZE * SIZE) as usize);
+    Ok(Icon::from_rgba(rgba, SIZE, SIZE)?)
+}